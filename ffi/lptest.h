@@ -0,0 +1,115 @@
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#ifndef LPTEST_H
+#define LPTEST_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum LpStatus {
+  LP_STATUS_OK,
+  /**
+   * The requests cannot be satisfied
+   */
+  LP_STATUS_NO_SOLUTION,
+  /**
+   * The state does not pass `State::validate`
+   */
+  LP_STATUS_INVALID_STATE,
+  /**
+   * A group or index out of range, or a connection of an index to itself
+   */
+  LP_STATUS_INVALID_ARGUMENT,
+  /**
+   * The backend panicked; no solution is returned
+   */
+  LP_STATUS_INTERNAL,
+} LpStatus;
+
+typedef enum LpBackend {
+  LP_BACKEND_HIGHS,
+  LP_BACKEND_MICROLP,
+} LpBackend;
+
+/**
+ * Opaque handle of an `HqSolution`
+ */
+typedef struct LpSolution LpSolution;
+
+/**
+ * Opaque handle of a `State`
+ */
+typedef struct LpState LpState;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+struct LpState *lp_state_new(void);
+
+void lp_state_free(struct LpState *state);
+
+/**
+ * Appends an empty group and returns its position
+ */
+size_t lp_state_add_group(struct LpState *state);
+
+/**
+ * Appends an index with the given free amount to `group`; its position is the number of
+ * indices added to the group before
+ */
+enum LpStatus lp_state_add_free(struct LpState *state,
+                                size_t group,
+                                uint32_t units,
+                                uint32_t fractions);
+
+enum LpStatus lp_state_set_request(struct LpState *state,
+                                   size_t group,
+                                   uint32_t units,
+                                   uint32_t fractions);
+
+/**
+ * Connects index `g1` of group `r1` with index `g2` of group `r2`; a weight of `-INFINITY`
+ * forbids selecting both
+ */
+enum LpStatus lp_state_add_connection(struct LpState *state,
+                                      size_t r1,
+                                      size_t g1,
+                                      size_t r2,
+                                      size_t g2,
+                                      double weight);
+
+/**
+ * Solves the state with the default options; on `LpStatus::Ok`, `*solution` is set to a new
+ * solution, otherwise to null
+ */
+enum LpStatus lp_solve(const struct LpState *state,
+                       enum LpBackend backend,
+                       struct LpSolution **solution);
+
+void lp_solution_free(struct LpSolution *solution);
+
+double lp_solution_objective(const struct LpSolution *solution);
+
+/**
+ * Number of selected indices in `group` (0 for an invalid group)
+ */
+size_t lp_solution_index_count(const struct LpSolution *solution, size_t group);
+
+/**
+ * Copies up to `len` selected indices of `group` in ascending order to `indices` and returns
+ * how many indices were selected in the group
+ */
+size_t lp_solution_indices(const struct LpSolution *solution,
+                           size_t group,
+                           size_t *indices,
+                           size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LPTEST_H */
@@ -0,0 +1,68 @@
+/* Exercises the C interface: cargo build --release && make -C ffi test */
+#include "lptest.h"
+
+#include <assert.h>
+#include <math.h>
+#include <stdio.h>
+
+static LpState *two_pairs(void) {
+    /* Four GPUs with 1 unit each, indices 0-1 and 2-3 are connected, 1-2 is forbidden */
+    LpState *state = lp_state_new();
+    size_t gpus = lp_state_add_group(state);
+    for (int i = 0; i < 4; i++) {
+        assert(lp_state_add_free(state, gpus, 1, 0) == LP_STATUS_OK);
+    }
+    assert(lp_state_add_connection(state, gpus, 0, gpus, 1, 100.0) == LP_STATUS_OK);
+    assert(lp_state_add_connection(state, gpus, 2, gpus, 3, 200.0) == LP_STATUS_OK);
+    assert(lp_state_add_connection(state, gpus, 1, gpus, 2, -INFINITY) == LP_STATUS_OK);
+    return state;
+}
+
+static void test_solve(LpBackend backend) {
+    LpState *state = two_pairs();
+    assert(lp_state_set_request(state, 0, 2, 0) == LP_STATUS_OK);
+
+    LpSolution *solution;
+    assert(lp_solve(state, backend, &solution) == LP_STATUS_OK);
+    size_t indices[4];
+    assert(lp_solution_indices(solution, 0, indices, 4) == 2);
+    assert(lp_solution_index_count(solution, 0) == 2);
+    assert(indices[0] == 2 && indices[1] == 3);
+    assert(lp_solution_objective(solution) < 0.0);
+    printf("backend %d: indices %zu %zu, objective %f\n", backend, indices[0], indices[1],
+           lp_solution_objective(solution));
+
+    /* Only as many indices as fit are copied */
+    assert(lp_solution_indices(solution, 0, indices, 1) == 2);
+    assert(lp_solution_indices(solution, 1, indices, 4) == 0);
+    lp_solution_free(solution);
+    lp_state_free(state);
+}
+
+static void test_errors(void) {
+    LpState *state = two_pairs();
+    assert(lp_state_add_free(state, 1, 1, 0) == LP_STATUS_INVALID_ARGUMENT);
+    assert(lp_state_set_request(state, 1, 1, 0) == LP_STATUS_INVALID_ARGUMENT);
+    assert(lp_state_add_connection(state, 0, 0, 0, 4, 1.0) == LP_STATUS_INVALID_ARGUMENT);
+    assert(lp_state_add_connection(state, 0, 0, 0, 0, 1.0) == LP_STATUS_INVALID_ARGUMENT);
+
+    LpSolution *solution;
+    assert(lp_state_set_request(state, 0, 5, 0) == LP_STATUS_OK);
+    assert(lp_solve(state, LP_BACKEND_MICROLP, &solution) == LP_STATUS_NO_SOLUTION);
+    assert(solution == NULL);
+
+    /* A duplicate connection only shows up when validating */
+    assert(lp_state_set_request(state, 0, 1, 0) == LP_STATUS_OK);
+    assert(lp_state_add_connection(state, 0, 1, 0, 0, 1.0) == LP_STATUS_OK);
+    assert(lp_solve(state, LP_BACKEND_MICROLP, &solution) == LP_STATUS_INVALID_STATE);
+    assert(solution == NULL);
+    lp_state_free(state);
+}
+
+int main(void) {
+    test_solve(LP_BACKEND_HIGHS);
+    test_solve(LP_BACKEND_MICROLP);
+    test_errors();
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,31 @@
+from ._lptest import (
+    BENCHMARKS,
+    Amount,
+    Connection,
+    Group,
+    Solution,
+    State,
+    antiaffinity_16_16,
+    benchmark,
+    diagnose,
+    random_18_18,
+    random_f_18_18,
+    solve,
+    tree_topology,
+)
+
+__all__ = [
+    "BENCHMARKS",
+    "Amount",
+    "Connection",
+    "Group",
+    "Solution",
+    "State",
+    "antiaffinity_16_16",
+    "benchmark",
+    "diagnose",
+    "random_18_18",
+    "random_f_18_18",
+    "solve",
+    "tree_topology",
+]
@@ -0,0 +1,385 @@
+//! Python bindings, built with `maturin develop` (or `maturin build`) in this directory. The
+//! package `lptest` re-exports this module `lptest._lptest`.
+//!
+//! `Amount`, `Connection`, `Group` and `Solution` are plain values: attributes return copies, so
+//! a changed group has to be assigned back, e.g. `state.groups = groups`.
+
+use lp::benchmarks;
+use lp::solver::{self, ObjectiveTerm, SolveOptions};
+use lp::state;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+fn value_error(error: impl ToString) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+fn validation_error(errors: &[state::ValidationError]) -> PyErr {
+    let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    value_error(errors.join("; "))
+}
+
+#[pyclass(get_all, set_all, eq, from_py_object, module = "lptest")]
+#[derive(Clone, PartialEq)]
+struct Amount {
+    units: u32,
+    fractions: u32,
+}
+
+#[pymethods]
+impl Amount {
+    #[new]
+    #[pyo3(signature = (units, fractions = 0))]
+    fn new(units: u32, fractions: u32) -> Self {
+        Amount { units, fractions }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Amount({}, {})", self.units, self.fractions)
+    }
+}
+
+impl From<&state::Amount> for Amount {
+    fn from(amount: &state::Amount) -> Self {
+        Amount::new(amount.units, amount.fractions)
+    }
+}
+
+impl From<&Amount> for state::Amount {
+    fn from(amount: &Amount) -> Self {
+        state::Amount::new(amount.units, amount.fractions)
+    }
+}
+
+/// A weight of `-inf` forbids selecting both indices
+#[pyclass(get_all, set_all, from_py_object, module = "lptest")]
+#[derive(Clone)]
+struct Connection {
+    r1: usize,
+    g1: usize,
+    r2: usize,
+    g2: usize,
+    weight: f64,
+}
+
+#[pymethods]
+impl Connection {
+    #[new]
+    fn new(r1: usize, g1: usize, r2: usize, g2: usize, weight: f64) -> Self {
+        Connection {
+            r1,
+            g1,
+            r2,
+            g2,
+            weight,
+        }
+    }
+
+    #[staticmethod]
+    fn forbidden(r1: usize, g1: usize, r2: usize, g2: usize) -> Self {
+        Connection::new(r1, g1, r2, g2, f64::NEG_INFINITY)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Connection({}, {}, {}, {}, {})",
+            self.r1, self.g1, self.r2, self.g2, self.weight
+        )
+    }
+}
+
+#[pyclass(get_all, set_all, from_py_object, module = "lptest")]
+#[derive(Clone)]
+struct Group {
+    free: Vec<Amount>,
+    request: Amount,
+    name: Option<String>,
+    /// Empty or one optional label per index
+    labels: Vec<Option<String>>,
+}
+
+#[pymethods]
+impl Group {
+    #[new]
+    #[pyo3(signature = (free, request = Amount::new(0, 0), name = None, labels = Vec::new()))]
+    fn new(
+        free: Vec<Amount>,
+        request: Amount,
+        name: Option<String>,
+        labels: Vec<Option<String>>,
+    ) -> Self {
+        Group {
+            free,
+            request,
+            name,
+            labels,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        let free: Vec<_> = self.free.iter().map(Amount::__repr__).collect();
+        let name = match &self.name {
+            Some(name) => format!("{name:?}"),
+            None => "None".to_string(),
+        };
+        format!(
+            "Group([{}], request={}, name={name})",
+            free.join(", "),
+            self.request.__repr__(),
+        )
+    }
+}
+
+#[pyclass(module = "lptest")]
+struct State(state::State);
+
+#[pymethods]
+impl State {
+    #[new]
+    #[pyo3(signature = (groups, connections = Vec::new()))]
+    fn new(groups: Vec<Group>, connections: Vec<Connection>) -> Self {
+        let mut state = State(state::State::default());
+        state.set_groups(groups);
+        state.set_connections(connections);
+        state
+    }
+
+    #[staticmethod]
+    fn from_json(text: &str) -> PyResult<Self> {
+        serde_json::from_str(text).map(State).map_err(value_error)
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(&self.0).unwrap()
+    }
+
+    #[getter]
+    fn groups(&self) -> Vec<Group> {
+        self.0
+            .groups
+            .iter()
+            .map(|g| Group {
+                free: g.free.iter().map(Amount::from).collect(),
+                request: Amount::from(&g.request),
+                name: g.name.clone(),
+                labels: g.labels.clone(),
+            })
+            .collect()
+    }
+
+    #[setter]
+    fn set_groups(&mut self, groups: Vec<Group>) {
+        self.0.groups = groups
+            .into_iter()
+            .map(|g| state::Group {
+                name: g.name,
+                free: g.free.iter().map(state::Amount::from).collect(),
+                request: state::Amount::from(&g.request),
+                labels: g.labels,
+            })
+            .collect();
+    }
+
+    #[getter]
+    fn connections(&self) -> Vec<Connection> {
+        self.0
+            .connections
+            .iter()
+            .map(|c| Connection::new(c.r1, c.g1, c.r2, c.g2, c.weight))
+            .collect()
+    }
+
+    #[setter]
+    fn set_connections(&mut self, connections: Vec<Connection>) {
+        self.0.connections = connections
+            .into_iter()
+            .map(|c| state::Connection {
+                r1: c.r1,
+                g1: c.g1,
+                r2: c.r2,
+                g2: c.g2,
+                weight: c.weight,
+            })
+            .collect();
+    }
+
+    /// Sets the request of every group, in the order of `groups`
+    fn set_requests(&mut self, requests: Vec<Amount>) -> PyResult<()> {
+        if requests.len() != self.0.groups.len() {
+            return Err(value_error(format!(
+                "Expected {} requests, got {}",
+                self.0.groups.len(),
+                requests.len()
+            )));
+        }
+        let requests: Vec<_> = requests.iter().map(state::Amount::from).collect();
+        self.0.set_requests(&requests);
+        Ok(())
+    }
+
+    /// Raises ValueError listing every problem of the state
+    fn validate(&self) -> PyResult<()> {
+        self.0
+            .validate()
+            .map_err(|errors| validation_error(&errors))
+    }
+
+    /// Takes the amounts of `solution` from the free amounts
+    fn allocate(&mut self, solution: &Solution) -> PyResult<()> {
+        self.0.allocate(&solution.to_hq()).map_err(value_error)
+    }
+
+    /// Returns the amounts of `solution` to the free amounts
+    fn release(&mut self, solution: &Solution) -> PyResult<()> {
+        self.0.release(&solution.to_hq()).map_err(value_error)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "State({} groups, {} connections)",
+            self.0.groups.len(),
+            self.0.connections.len()
+        )
+    }
+}
+
+/// Selected indices and the amounts taken from them per group
+#[pyclass(get_all, module = "lptest")]
+struct Solution {
+    groups: Vec<Vec<usize>>,
+    amounts: Vec<Vec<Amount>>,
+    objective: f64,
+}
+
+impl Solution {
+    fn to_hq(&self) -> solver::HqSolution {
+        solver::HqSolution {
+            groups: self.groups.clone(),
+            amounts: self
+                .amounts
+                .iter()
+                .map(|a| a.iter().map(state::Amount::from).collect())
+                .collect(),
+            objective: self.objective,
+        }
+    }
+}
+
+#[pymethods]
+impl Solution {
+    fn __repr__(&self) -> String {
+        format!(
+            "Solution(groups={:?}, objective={})",
+            self.groups, self.objective
+        )
+    }
+}
+
+/// Solves `state` with the backend "highs" or "microlp"; returns None when the requests cannot
+/// be satisfied. The keyword arguments are the options of `lptest solve`, `lexicographic` takes
+/// term names such as "affinity".
+#[pyfunction]
+#[pyo3(signature = (
+    state,
+    backend = "highs",
+    *,
+    symmetry_breaking = false,
+    aggregate_connections = false,
+    fragmentation = None,
+    lexicographic = Vec::new(),
+    canonical = false,
+))]
+#[allow(clippy::too_many_arguments)]
+fn solve(
+    py: Python<'_>,
+    state: &State,
+    backend: &str,
+    symmetry_breaking: bool,
+    aggregate_connections: bool,
+    fragmentation: Option<f64>,
+    lexicographic: Vec<String>,
+    canonical: bool,
+) -> PyResult<Option<Solution>> {
+    let options = SolveOptions {
+        symmetry_breaking,
+        aggregate_connections,
+        fragmentation,
+        lexicographic: lexicographic
+            .iter()
+            .map(|term| term.parse::<ObjectiveTerm>())
+            .collect::<Result<_, _>>()
+            .map_err(value_error)?,
+        canonical,
+    };
+    let state = &state.0;
+    let solution = match backend {
+        "highs" => py.detach(|| solver::solve(state, good_lp::solvers::highs::highs, &options)),
+        "microlp" => {
+            py.detach(|| solver::solve(state, good_lp::solvers::microlp::microlp, &options))
+        }
+        _ => return Err(value_error(format!("Unknown backend: {backend}"))),
+    };
+    let solution = solution.map_err(|errors| validation_error(&errors))?;
+    Ok(solution.map(|s| Solution {
+        groups: s.groups,
+        amounts: s
+            .amounts
+            .iter()
+            .map(|a| a.iter().map(Amount::from).collect())
+            .collect(),
+        objective: s.objective,
+    }))
+}
+
+/// Reasons why the requests of `state` cannot be satisfied
+#[pyfunction]
+fn diagnose(state: &State) -> Vec<String> {
+    solver::diagnose(&state.0)
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// One of the benchmarks of `lptest <solver> <benchmark>`, see `BENCHMARKS`
+#[pyfunction]
+fn benchmark(name: &str) -> PyResult<State> {
+    benchmarks::create(name).map(State).map_err(value_error)
+}
+
+#[pyfunction]
+fn random_18_18(seed: u64, p: f64) -> State {
+    State(benchmarks::create_random_18_18(seed, p))
+}
+
+#[pyfunction]
+fn random_f_18_18(seed: u64, p: f64) -> State {
+    State(benchmarks::create_random_f_18_18(seed, p))
+}
+
+#[pyfunction]
+fn antiaffinity_16_16(seed: u64, p: f64) -> State {
+    State(benchmarks::create_antiaffinity_16_16(seed, p))
+}
+
+#[pyfunction]
+fn tree_topology(branching: Vec<usize>, request: u32, seed: u64) -> State {
+    State(benchmarks::create_tree_topology(&branching, request, seed))
+}
+
+#[pymodule]
+fn _lptest(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Amount>()?;
+    m.add_class::<Connection>()?;
+    m.add_class::<Group>()?;
+    m.add_class::<State>()?;
+    m.add_class::<Solution>()?;
+    m.add("BENCHMARKS", benchmarks::NAMES.to_vec())?;
+    m.add_function(wrap_pyfunction!(solve, m)?)?;
+    m.add_function(wrap_pyfunction!(diagnose, m)?)?;
+    m.add_function(wrap_pyfunction!(benchmark, m)?)?;
+    m.add_function(wrap_pyfunction!(random_18_18, m)?)?;
+    m.add_function(wrap_pyfunction!(random_f_18_18, m)?)?;
+    m.add_function(wrap_pyfunction!(antiaffinity_16_16, m)?)?;
+    m.add_function(wrap_pyfunction!(tree_topology, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,117 @@
+import math
+
+import pytest
+
+import lptest
+from lptest import Amount, Connection, Group, State
+
+BACKENDS = ["highs", "microlp"]
+
+
+def pairs():
+    # Indices 0-1 and 2-3 are connected, 1-2 must not be selected together
+    return State(
+        [Group([Amount(1)] * 4, request=Amount(2), name="gpus")],
+        [
+            Connection(0, 0, 0, 1, 100.0),
+            Connection(0, 2, 0, 3, 200.0),
+            Connection.forbidden(0, 1, 0, 2),
+        ],
+    )
+
+
+@pytest.mark.parametrize("backend", BACKENDS)
+def test_solve(backend):
+    solution = lptest.solve(pairs(), backend)
+    assert solution.groups == [[2, 3]]
+    assert solution.amounts == [[Amount(1), Amount(1)]]
+    assert solution.objective < 0
+
+
+@pytest.mark.parametrize("backend", BACKENDS)
+def test_benchmark(backend):
+    state = lptest.benchmark("tree_4_4")
+    assert state.groups[0].name == "gpus"
+    solution = lptest.solve(state, backend)
+    assert sum(len(g) for g in solution.groups) == 4
+
+
+def test_benchmark_names():
+    assert "empty_4" in lptest.BENCHMARKS
+    for name in ["empty_4", "fractions_16", "tree_2_4_4"]:
+        lptest.benchmark(name).validate()
+    with pytest.raises(ValueError):
+        lptest.benchmark("nonexistent")
+
+
+def test_parametric_benchmarks():
+    assert len(lptest.random_18_18(1, 0.5).groups) == 2
+    assert lptest.random_18_18(1, 0.5).to_json() == lptest.random_18_18(1, 0.5).to_json()
+    lptest.random_f_18_18(2, 0.5).validate()
+    lptest.antiaffinity_16_16(3, 0.2).validate()
+    assert len(lptest.tree_topology([2, 4], 3, 0).groups[0].free) == 8
+
+
+def test_no_solution():
+    state = pairs()
+    state.set_requests([Amount(5)])
+    assert lptest.solve(state, "microlp") is None
+    assert lptest.diagnose(state) == ["group 0: requested 5 units, only 4 free (short by 1)"]
+
+
+def test_invalid():
+    state = pairs()
+    state.connections = state.connections + [Connection(0, 0, 0, 9, 1.0)]
+    with pytest.raises(ValueError, match="does not exist"):
+        state.validate()
+    with pytest.raises(ValueError):
+        lptest.solve(state)
+    with pytest.raises(ValueError, match="backend"):
+        lptest.solve(pairs(), "cplex")
+    with pytest.raises(ValueError):
+        lptest.solve(pairs(), lexicographic=["speed"])
+    with pytest.raises(ValueError):
+        pairs().set_requests([Amount(1), Amount(1)])
+
+
+def test_options():
+    state = pairs()
+    state.set_requests([Amount(1)])
+    # Every single index is optimal, canonical solving picks the first one
+    for backend in BACKENDS:
+        solution = lptest.solve(
+            state, backend, lexicographic=["index-count", "affinity"], canonical=True
+        )
+        assert solution.groups == [[0]]
+    solution = lptest.solve(state, "microlp", symmetry_breaking=True, fragmentation=8.0)
+    assert len(solution.groups[0]) == 1
+
+
+def test_allocate_release():
+    state = pairs()
+    solution = lptest.solve(state, "microlp")
+    state.allocate(solution)
+    assert [a.units for a in state.groups[0].free] == [1, 1, 0, 0]
+    state.release(solution)
+    assert [a.units for a in state.groups[0].free] == [1, 1, 1, 1]
+    smaller = State([Group([Amount(1)])])
+    with pytest.raises(ValueError, match="does not exist"):
+        smaller.release(solution)
+
+
+def test_values_are_copies():
+    state = pairs()
+    groups = state.groups
+    groups[0].request = Amount(3)
+    assert state.groups[0].request == Amount(2)
+    state.groups = groups
+    assert state.groups[0].request == Amount(3)
+    assert math.isinf(state.connections[2].weight)
+
+
+def test_json_roundtrip():
+    state = State.from_json(pairs().to_json())
+    assert state.to_json() == pairs().to_json()
+    assert '"forbidden"' in state.to_json()
+    with pytest.raises(ValueError):
+        State.from_json("{}")
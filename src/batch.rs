@@ -0,0 +1,259 @@
+//! Solving many instance files (one `State` as JSON each) on a pool of threads.
+
+use crate::service::{Outcome, solve_request};
+use crate::solver::HqSolution;
+use crate::state::State;
+use serde::Serialize;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const CSV_HEADER: &str = "file,status,time_ms,objective,indices,detail";
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub threads: usize,
+    /// Time limit per instance. A solve that exceeds it is reported as `Outcome::Timeout` and
+    /// left running in the background, since the backends cannot be interrupted. At most
+    /// `threads` solves are left running, beyond that a worker waits for its timed out solve
+    /// before it takes the next file.
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Serialize)]
+pub struct BatchResult {
+    pub file: PathBuf,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+    pub time_ms: f64,
+}
+
+impl BatchResult {
+    /// One line of CSV under `CSV_HEADER`, with the indices of a group separated by spaces and
+    /// the groups by `;`
+    pub fn to_csv(&self) -> String {
+        let (status, objective, indices, detail) = match &self.outcome {
+            Outcome::Solved { solution } => {
+                let groups: Vec<String> = solution
+                    .groups
+                    .iter()
+                    .map(|g| {
+                        let g: Vec<String> = g.iter().map(|i| i.to_string()).collect();
+                        g.join(" ")
+                    })
+                    .collect();
+                (
+                    "solved",
+                    solution.objective.to_string(),
+                    groups.join(";"),
+                    String::new(),
+                )
+            }
+            Outcome::NoSolution { reasons } => (
+                "no_solution",
+                String::new(),
+                String::new(),
+                reasons.join("; "),
+            ),
+            Outcome::Error { error } => ("error", String::new(), String::new(), error.clone()),
+            Outcome::Timeout => ("timeout", String::new(), String::new(), String::new()),
+        };
+        format!(
+            "{},{},{},{},{},{}",
+            csv_field(&self.file.display().to_string()),
+            status,
+            self.time_ms,
+            objective,
+            indices,
+            csv_field(&detail)
+        )
+    }
+}
+
+/// `text` as one CSV field, quoted when it contains a separator, quote or line break
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Counts of the outcomes and solve times of a batch
+#[derive(Debug, Default, Clone)]
+pub struct BatchSummary {
+    pub solved: usize,
+    pub no_solution: usize,
+    pub errors: usize,
+    pub timeouts: usize,
+    /// Times of the solved and unsolvable instances in milliseconds
+    pub times_ms: Vec<f64>,
+}
+
+impl BatchSummary {
+    pub fn add(&mut self, result: &BatchResult) {
+        match result.outcome {
+            Outcome::Solved { .. } => self.solved += 1,
+            Outcome::NoSolution { .. } => self.no_solution += 1,
+            Outcome::Error { .. } => self.errors += 1,
+            Outcome::Timeout => self.timeouts += 1,
+        }
+        if matches!(
+            result.outcome,
+            Outcome::Solved { .. } | Outcome::NoSolution { .. }
+        ) {
+            self.times_ms.push(result.time_ms);
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.solved + self.no_solution + self.errors + self.timeouts
+    }
+}
+
+/// The `*.json` files in `dir`, sorted by name
+pub fn instance_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Solves every file on `options.threads` threads and calls `on_result` in the calling thread
+/// in order of completion. Unreadable files and invalid states are reported as
+/// `Outcome::Error` and do not stop the batch.
+pub fn solve_batch<S>(
+    files: &[PathBuf],
+    options: &BatchOptions,
+    solve: S,
+    mut on_result: impl FnMut(BatchResult),
+) where
+    S: Fn(&State) -> Result<Option<HqSolution>, String> + Send + Sync + 'static,
+{
+    let solve = Arc::new(solve);
+    let next = AtomicUsize::new(0);
+    let abandoned = Abandoned {
+        count: Arc::new(AtomicUsize::new(0)),
+        limit: options.threads.max(1),
+    };
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            let sender = sender.clone();
+            let (solve, next, abandoned) = (&solve, &next, &abandoned);
+            scope.spawn(move || {
+                while let Some(file) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let (result, pending) = solve_file(file, options.timeout, solve, abandoned);
+                    if sender.send(result).is_err() {
+                        break;
+                    }
+                    if let Some(pending) = pending {
+                        let _ = pending.recv();
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for result in receiver {
+            on_result(result);
+        }
+    });
+}
+
+/// Solves left running after a timeout
+struct Abandoned {
+    count: Arc<AtomicUsize>,
+    limit: usize,
+}
+
+/// The result of `file`, and the timed out solve to wait for when too many are abandoned
+fn solve_file<S>(
+    file: &Path,
+    timeout: Option<Duration>,
+    solve: &Arc<S>,
+    abandoned: &Abandoned,
+) -> (BatchResult, Option<Receiver<Outcome>>)
+where
+    S: Fn(&State) -> Result<Option<HqSolution>, String> + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    let mut pending = None;
+    let state = std::fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|text| serde_json::from_str::<State>(&text).map_err(|e| e.to_string()));
+    let outcome = match (state, timeout) {
+        (Err(error), _) => Outcome::Error { error },
+        (Ok(state), None) => solve_guarded(&state, solve.as_ref()),
+        (Ok(state), Some(timeout)) => {
+            let (sender, receiver) = mpsc::channel();
+            // Set when the worker stops waiting, the solve thread then releases the count
+            let stopped = Arc::new(Mutex::new(false));
+            let solve = Arc::clone(solve);
+            let (thread_stopped, count) = (Arc::clone(&stopped), Arc::clone(&abandoned.count));
+            thread::spawn(move || {
+                let outcome = solve_guarded(&state, solve.as_ref());
+                // Held while sending, so the worker sees either the outcome or no result yet
+                let stopped = thread_stopped.lock().unwrap();
+                if *stopped {
+                    count.fetch_sub(1, Ordering::SeqCst);
+                } else {
+                    let _ = sender.send(outcome);
+                }
+            });
+            match receiver.recv_timeout(timeout.saturating_sub(start.elapsed())) {
+                Ok(outcome) => outcome,
+                Err(RecvTimeoutError::Timeout) => {
+                    // Locked so the solve thread cannot finish in between
+                    let mut stopped = stopped.lock().unwrap();
+                    if let Ok(outcome) = receiver.try_recv() {
+                        outcome
+                    } else {
+                        let abandon = abandoned
+                            .count
+                            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                                (n < abandoned.limit).then_some(n + 1)
+                            })
+                            .is_ok();
+                        if abandon {
+                            *stopped = true;
+                        } else {
+                            pending = Some(receiver);
+                        }
+                        Outcome::Timeout
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => Outcome::Error {
+                    error: "Solver thread ended without a result".to_string(),
+                },
+            }
+        }
+    };
+    let result = BatchResult {
+        file: file.to_path_buf(),
+        outcome,
+        time_ms: start.elapsed().as_secs_f64() * 1000.0,
+    };
+    (result, pending)
+}
+
+/// Reports a panic of the backend as `Outcome::Error` instead of ending the worker
+fn solve_guarded(
+    state: &State,
+    solve: impl Fn(&State) -> Result<Option<HqSolution>, String>,
+) -> Outcome {
+    panic::catch_unwind(AssertUnwindSafe(|| solve_request(state, solve))).unwrap_or_else(|_| {
+        Outcome::Error {
+            error: "Solver panicked".to_string(),
+        }
+    })
+}
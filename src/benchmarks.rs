@@ -0,0 +1,410 @@
+//! Benchmark instances used by the CLI, each a `State` with requests set.
+
+use crate::builder::StateBuilder;
+use crate::state::{Amount, State};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Names accepted by `create`
+pub const NAMES: &[&str] = &[
+    "empty_4",
+    "empty_16",
+    "empty_32",
+    "empty_8_8",
+    "empty_8_8_8",
+    "onlyo_4",
+    "onlyo_16",
+    "onlyo_32",
+    "primes_16",
+    "primes_16_n",
+    "triplets_16",
+    "one2one_12_12",
+    "one2one_8_8_8",
+    "all2all_16_16",
+    "stairs_32_16_8",
+    "diamonds_16_32_16",
+    "random_18_18_v1",
+    "random_18_18_v2",
+    "random_18_18_v3",
+    "empty_f_16",
+    "fractions_16",
+    "random_f_18_18_v1",
+    "random_f_18_18_v2",
+    "antiaffinity_16_16_v1",
+    "antiaffinity_16_16_v2",
+    "forbidden_16_16",
+    "locality_32",
+    "tree_4_4",
+    "tree_2_4_4",
+    "tree_2_2_4_2",
+];
+
+/// Creates the benchmark with the given name
+pub fn create(name: &str) -> Result<State, String> {
+    Ok(match name {
+        "empty_4" => create_empty_4(),
+        "empty_16" => create_empty_16(),
+        "empty_32" => create_empty_32(),
+        "empty_8_8" => create_empty_8_8(),
+        "empty_8_8_8" => create_empty_8_8_8(),
+        "onlyo_4" => create_onlyo_4(),
+        "onlyo_16" => create_onlyo_16(),
+        "onlyo_32" => create_onlyo_32(),
+        "primes_16" => create_primes_16(),
+        "primes_16_n" => create_primes_16_n(),
+        "triplets_16" => create_triplets_16(),
+        "one2one_12_12" => create_one2one_12_12(),
+        "one2one_8_8_8" => create_one2one_8_8_8(),
+        "all2all_16_16" => create_empty_16(),
+        "stairs_32_16_8" => create_stairs_32_16_8(),
+        "diamonds_16_32_16" => create_diamonds_32_16_32(),
+        "random_18_18_v1" => create_random_18_18(1234, 0.5),
+        "random_18_18_v2" => create_random_18_18(4242424242, 0.2),
+        "random_18_18_v3" => create_random_18_18(8888881, 0.8),
+        "empty_f_16" => create_empty_16(),
+        "fractions_16" => create_fractions_16(),
+        "random_f_18_18_v1" => create_random_18_18(22221, 0.20),
+        "random_f_18_18_v2" => create_random_18_18(33331, 0.60),
+        "antiaffinity_16_16_v1" => create_antiaffinity_16_16(5151, 0.3),
+        "antiaffinity_16_16_v2" => create_antiaffinity_16_16(727272, 0.7),
+        "forbidden_16_16" => create_forbidden_16_16(),
+        "locality_32" => create_locality_32(),
+        "tree_4_4" => create_tree_topology(&[4, 4], 4, 31),
+        "tree_2_4_4" => create_tree_topology(&[2, 4, 4], 6, 32),
+        "tree_2_2_4_2" => create_tree_topology(&[2, 2, 4, 2], 10, 33),
+        _ => return Err(format!("Invalid benchmark name: {}", name)),
+    })
+}
+
+pub fn create_empty_4() -> State {
+    StateBuilder::new()
+        .group(|g| g.free_units([4; 4]).request(7))
+        .build()
+        .unwrap()
+}
+
+pub fn create_empty_16() -> State {
+    StateBuilder::new()
+        .group(|g| g.free_units([24; 16]).request(100))
+        .build()
+        .unwrap()
+}
+
+pub fn create_empty_8_8() -> State {
+    StateBuilder::new()
+        .group(|g| g.free_units([24; 8]).request(12))
+        .group(|g| g.free_units([32; 8]).request(74))
+        .build()
+        .unwrap()
+}
+
+pub fn create_empty_8_8_8() -> State {
+    StateBuilder::new()
+        .group(|g| g.free_units([24; 8]).request(48))
+        .group(|g| g.free_units([32; 8]).request(74))
+        .group(|g| g.free_units([4; 8]).request(30))
+        .build()
+        .unwrap()
+}
+
+pub fn create_empty_32() -> State {
+    StateBuilder::new()
+        .group(|g| g.free_units([120; 32]).request(921))
+        .build()
+        .unwrap()
+}
+
+pub fn create_onlyo_16() -> State {
+    StateBuilder::new()
+        .group(|g| {
+            g.free_units([4, 0, 2, 1, 4, 0, 2, 1, 0, 0, 0, 0, 3, 2, 3, 2])
+                .request(24)
+        })
+        .build()
+        .unwrap()
+}
+
+pub fn create_onlyo_4() -> State {
+    StateBuilder::new()
+        .group(|g| g.free_units([1, 0, 2, 1]).request(7))
+        .build()
+        .unwrap()
+}
+
+pub fn create_onlyo_32() -> State {
+    let free = [4, 0, 2, 1, 4, 0, 2, 1, 0, 0, 0, 0, 3, 2, 3, 2];
+    StateBuilder::new()
+        .group(|g| g.free_units(free).free_units(free).request(48))
+        .build()
+        .unwrap()
+}
+
+pub fn create_primes_16() -> State {
+    StateBuilder::new()
+        .group(|g| {
+            g.free_units([2, 3, 5, 7, 9, 11, 13, 17, 19, 23, 29, 31, 37, 39, 41])
+                .request(113)
+        })
+        .build()
+        .unwrap()
+}
+
+pub fn create_primes_16_n() -> State {
+    StateBuilder::new()
+        .group(|g| {
+            g.free_units([2, 3, 5, 7, 9, 11, 13, 17, 19, 23, 29, 31, 37, 39, 41])
+                .request(329)
+        })
+        .build()
+        .unwrap()
+}
+
+pub fn create_triplets_16() -> State {
+    StateBuilder::new()
+        .group(|g| {
+            g.free_units([40, 41, 42, 40, 41, 42, 39, 40, 41, 43, 44, 45, 38, 39, 40])
+                .request(399)
+        })
+        .build()
+        .unwrap()
+}
+
+pub fn create_one2one_12_12() -> State {
+    let mut builder = StateBuilder::new()
+        .group(|g| {
+            g.free_units([4, 4, 4, 4, 3, 3, 3, 3, 3, 2, 5, 1])
+                .request(7)
+        })
+        .group(|g| {
+            g.free_units([2, 2, 2, 2, 3, 3, 3, 3, 1, 0, 0, 1])
+                .request(7)
+        });
+    for i in 0..12 {
+        builder = builder.connect((0, i), (1, i), 256.0);
+    }
+    builder.build().unwrap()
+}
+
+pub fn create_one2one_8_8_8() -> State {
+    let mut builder = StateBuilder::new()
+        .group(|g| g.free_units([4, 4, 4, 4, 3, 3, 3, 3]).request(7))
+        .group(|g| g.free_units([2, 2, 2, 2, 3, 3, 3, 3]).request(7))
+        .group(|g| g.free_units([2, 6, 2, 3, 2, 2, 3, 3]).request(12));
+    for i in 0..8 {
+        builder = builder.connect((0, i), (1, i), 256.0);
+    }
+    for i in 0..8 {
+        builder = builder.connect((1, i), (2, i), 256.0);
+    }
+    builder.build().unwrap()
+}
+
+pub fn create_all2all_16_16() -> State {
+    let mut builder = StateBuilder::new()
+        .group(|g| {
+            g.free_units([1, 4, 3, 4, 5, 3, 3, 3, 1, 4, 3, 4, 5, 3, 3, 3])
+                .request(14)
+        })
+        .group(|g| {
+            g.free_units([2, 2, 2, 2, 3, 3, 3, 3, 0, 0, 0, 5, 1, 3, 1, 1])
+                .request(9)
+        });
+    for i in 0..16 {
+        for j in 0..i {
+            builder = builder.connect((0, i), (1, j), ((i + j) % 2) as f64 * 128.0 + 128.0);
+        }
+    }
+    builder.build().unwrap()
+}
+
+pub fn create_stairs_32_16_8() -> State {
+    let mut builder = StateBuilder::new()
+        .group(|g| g.free_units(0..32).request(127))
+        .group(|g| {
+            g.free_units([2, 2, 2, 2, 3, 3, 3, 3, 0, 0, 0, 5, 1, 3, 1, 1])
+                .request(19)
+        })
+        .group(|g| g.free_units([2, 2, 2, 2, 3, 3, 3, 5]).request(10));
+    for i in 0..16 {
+        builder = builder.connect((0, i * 2), (1, i), 256.0);
+    }
+    for i in 0..8 {
+        builder = builder.connect((1, i * 2), (2, i), 348.0);
+    }
+    builder.build().unwrap()
+}
+
+pub fn create_diamonds_32_16_32() -> State {
+    let mut builder = StateBuilder::new()
+        .group(|g| g.free_units([4; 32]).request(7))
+        .group(|g| g.free_units([4; 32]).request(7))
+        .group(|g| g.free_units([4; 32]).request(7));
+    for i in 0..16 {
+        builder = builder.connect((0, i * 2), (1, i), 256.0);
+    }
+    for i in 0..16 {
+        builder = builder.connect((2, i * 2), (1, i), 348.0);
+    }
+    builder.build().unwrap()
+}
+
+/// Random connections between two groups of 18 indices, drawn before the free amounts
+fn random_connections_18_18(rng: &mut StdRng, p: f64) -> Vec<(usize, usize, f64)> {
+    let mut connections = Vec::new();
+    for i in 0..18 {
+        for j in 0..i {
+            if rng.random_bool(p) {
+                connections.push((i, j, 64.0 * rng.random_range(1..6) as f64));
+            }
+        }
+    }
+    connections
+}
+
+pub fn create_random_18_18(seed: u64, p: f64) -> State {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let connections = random_connections_18_18(&mut rng, p);
+    let mut builder = StateBuilder::new()
+        .group(|g| {
+            g.free_units((0..18).map(|_| rng.random_range(1..=4)))
+                .request(12)
+        })
+        .group(|g| {
+            g.free_units((0..18).map(|_| rng.random_range(1..=4)))
+                .request(12)
+        });
+    for (i, j, weight) in connections {
+        builder = builder.connect((0, i), (1, j), weight);
+    }
+    builder.build().unwrap()
+}
+
+pub fn create_empty_f_16() -> State {
+    StateBuilder::new()
+        .group(|g| g.free_units([24; 8]).request_amount(Amount::new(100, 500)))
+        .build()
+        .unwrap()
+}
+
+pub fn create_fractions_16() -> State {
+    StateBuilder::new()
+        .group(|g| {
+            g.free([
+                Amount::new(2, 5000), // 0
+                Amount::new(1, 0),
+                Amount::new(2, 5500),
+                Amount::new(1, 2500),
+                Amount::new(1, 3000), // 4
+                Amount::new(0, 3600),
+                Amount::new(8, 0),
+                Amount::new(1, 6500),
+                Amount::new(2, 5000), // 8
+                Amount::new(1, 0),
+                Amount::new(2, 9000),
+                Amount::new(1, 2500),
+                Amount::new(1, 3000), // 12
+                Amount::new(0, 3500),
+                Amount::new(0, 9500),
+                Amount::new(1, 4500),
+            ])
+            .request_amount(Amount::new(12, 3500))
+        })
+        .build()
+        .unwrap()
+}
+
+pub fn create_random_f_18_18(seed: u64, p: f64) -> State {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let connections = random_connections_18_18(&mut rng, p);
+    let mut builder = StateBuilder::new()
+        .group(|g| {
+            g.free(
+                (0..18).map(|_| Amount::new(rng.random_range(1..=4), rng.random_range(0..10_000))),
+            )
+            .request_amount(Amount::new(12, 4999))
+        })
+        .group(|g| {
+            g.free_units((0..18).map(|_| rng.random_range(1..=4)))
+                .request_amount(Amount::new(12, 6010))
+        });
+    for (i, j, weight) in connections {
+        builder = builder.connect((0, i), (1, j), weight);
+    }
+    builder.build().unwrap()
+}
+
+pub fn create_antiaffinity_16_16(seed: u64, p: f64) -> State {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut connections = Vec::new();
+    for i in 0..16 {
+        for j in 0..16 {
+            if rng.random_bool(p) {
+                connections.push((i, j, 64.0 * rng.random_range(-4..4) as f64));
+            }
+        }
+    }
+    let mut builder = StateBuilder::new()
+        .group(|g| {
+            g.free_units((0..16).map(|_| rng.random_range(1..=4)))
+                .request(10)
+        })
+        .group(|g| {
+            g.free_units((0..16).map(|_| rng.random_range(1..=4)))
+                .request(10)
+        });
+    for (i, j, weight) in connections {
+        builder = builder.connect((0, i), (1, j), weight);
+    }
+    builder.build().unwrap()
+}
+
+pub fn create_forbidden_16_16() -> State {
+    let mut builder = StateBuilder::new()
+        .group(|g| g.free_units([4; 16]).request(22))
+        .group(|g| g.free_units((0..16).map(|i| i % 4 + 1)).request(14));
+    // Index i of both groups shares a bus, the neighbour is preferred
+    for i in 0..16 {
+        builder = builder.forbid((0, i), (1, i));
+    }
+    for i in 0..16 {
+        builder = builder.connect((0, i), (1, (i + 1) % 16), 256.0);
+    }
+    builder.build().unwrap()
+}
+
+pub fn create_locality_32() -> State {
+    let mut builder = StateBuilder::new().group(|g| {
+        g.free_units((0..32).map(|i| if i % 7 == 3 { 0 } else { i % 3 + 1 }))
+            .request(20)
+    });
+    // Cores closer to each other share more caches
+    for i in 0..32 {
+        for j in i + 1..32.min(i + 4) {
+            builder = builder.connect((0, i), (0, j), 192.0 / (j - i) as f64);
+        }
+    }
+    builder.build().unwrap()
+}
+
+/// One group of leaves (GPUs `gpu0`, `gpu1`, ...) in a tree with `branching` children per level, top first.
+/// Every inner node below the root gets a hyper connection over its leaves, weighted by its size,
+/// so allocations that fill whole subtrees are preferred. Some leaves are randomly busy.
+pub fn create_tree_topology(branching: &[usize], request: u32, seed: u64) -> State {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let leaves: usize = branching.iter().product();
+    let mut builder = StateBuilder::new().named_group("gpus", |g| {
+        (0..leaves)
+            .fold(g, |g, i| {
+                let free = if rng.random_bool(0.25) { 0 } else { 1 };
+                g.index(&format!("gpu{i}"), Amount::new_u(free))
+            })
+            .request(request)
+    });
+    for depth in 1..branching.len() {
+        let size: usize = branching[depth..].iter().product();
+        for start in (0..leaves).step_by(size) {
+            builder = builder.hyper((start..start + size).map(|i| (0, i)), 32.0 * size as f64);
+        }
+    }
+    builder.build().unwrap()
+}
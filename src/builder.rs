@@ -0,0 +1,185 @@
+//! Fluent construction of a `State`, e.g.
+//! `StateBuilder::new().group(|g| g.free_units([4, 4, 3]).request(7)).connect((0, 1), (1, 1), 256.0)`
+
+use crate::state::{Amount, Connection, Group, HyperConnection, State, ValidationError};
+
+/// Index given by position (group, index) or by group name and index label. Indices without a
+/// label can be referred to by their position written as a string, e.g. `("cpus", "1")`.
+#[derive(Debug, Clone)]
+pub enum IndexRef {
+    Position(usize, usize),
+    Name(String, String),
+}
+
+impl From<(usize, usize)> for IndexRef {
+    fn from((group, index): (usize, usize)) -> Self {
+        IndexRef::Position(group, index)
+    }
+}
+
+impl From<(&str, &str)> for IndexRef {
+    fn from((group, index): (&str, &str)) -> Self {
+        IndexRef::Name(group.to_string(), index.to_string())
+    }
+}
+
+#[derive(Default)]
+pub struct GroupBuilder {
+    group: Group,
+}
+
+impl GroupBuilder {
+    /// Appends indices with the given free amounts
+    pub fn free(mut self, amounts: impl IntoIterator<Item = Amount>) -> Self {
+        for amount in amounts {
+            self.group.free.push(amount);
+            self.group.labels.push(None);
+        }
+        self
+    }
+
+    /// Appends indices with the given free whole units
+    pub fn free_units(self, units: impl IntoIterator<Item = u32>) -> Self {
+        self.free(units.into_iter().map(Amount::new_u))
+    }
+
+    /// Appends one labelled index
+    pub fn index(mut self, label: &str, free: Amount) -> Self {
+        self.group.free.push(free);
+        self.group.labels.push(Some(label.to_string()));
+        self
+    }
+
+    pub fn request(self, units: u32) -> Self {
+        self.request_amount(Amount::new_u(units))
+    }
+
+    pub fn request_amount(mut self, request: Amount) -> Self {
+        self.group.request = request;
+        self
+    }
+}
+
+/// Builds a `State` group by group. Connections are checked when added, so they have to come
+/// after the groups they refer to; `build` reports all problems found, including duplicate
+/// names found by `State::validate`.
+#[derive(Default)]
+pub struct StateBuilder {
+    state: State,
+    errors: Vec<ValidationError>,
+}
+
+impl StateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn group(self, f: impl FnOnce(GroupBuilder) -> GroupBuilder) -> Self {
+        self.add_group(None, f(GroupBuilder::default()))
+    }
+
+    pub fn named_group(self, name: &str, f: impl FnOnce(GroupBuilder) -> GroupBuilder) -> Self {
+        self.add_group(Some(name.to_string()), f(GroupBuilder::default()))
+    }
+
+    fn add_group(mut self, name: Option<String>, group: GroupBuilder) -> Self {
+        let mut group = group.group;
+        if group.labels.iter().all(|l| l.is_none()) {
+            group.labels.clear();
+        }
+        group.name = name;
+        self.state.groups.push(group);
+        self
+    }
+
+    /// Position of an index; `n` is the number of the connection (or hyper connection, if
+    /// `hyper`) it is resolved for
+    fn resolve(&mut self, index: IndexRef, n: usize, hyper: bool) -> Option<(usize, usize)> {
+        match index {
+            IndexRef::Position(r, g) => {
+                if self
+                    .state
+                    .groups
+                    .get(r)
+                    .is_some_and(|group| g < group.free.len())
+                {
+                    Some((r, g))
+                } else if hyper {
+                    self.errors.push(ValidationError::UnknownHyperMember {
+                        hyper: n,
+                        group: r,
+                        index: g,
+                    });
+                    None
+                } else {
+                    self.errors.push(ValidationError::UnknownEndpoint {
+                        connection: n,
+                        group: r,
+                        index: g,
+                    });
+                    None
+                }
+            }
+            IndexRef::Name(group, index) => match self.state.index_by_name(&group, &index) {
+                Ok(position) => Some(position),
+                Err(error) => {
+                    self.errors.push(error);
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn connect(mut self, a: impl Into<IndexRef>, b: impl Into<IndexRef>, weight: f64) -> Self {
+        let n = self.state.connections.len();
+        let a = self.resolve(a.into(), n, false);
+        let b = self.resolve(b.into(), n, false);
+        if let (Some((r1, g1)), Some((r2, g2))) = (a, b) {
+            // Not Connection::new, which panics on a connection to itself
+            self.state.connections.push(Connection {
+                r1,
+                g1,
+                r2,
+                g2,
+                weight,
+            });
+        }
+        self
+    }
+
+    /// Adds a forbidden pair: both indices must never be selected together
+    pub fn forbid(self, a: impl Into<IndexRef>, b: impl Into<IndexRef>) -> Self {
+        self.connect(a, b, f64::NEG_INFINITY)
+    }
+
+    pub fn hyper<R: Into<IndexRef>>(
+        mut self,
+        members: impl IntoIterator<Item = R>,
+        weight: f64,
+    ) -> Self {
+        let n = self.state.hyper_connections.len();
+        let members: Vec<_> = members
+            .into_iter()
+            .map(|m| self.resolve(m.into(), n, true))
+            .collect();
+        if members.iter().all(Option::is_some) {
+            self.state.hyper_connections.push(HyperConnection {
+                members: members.into_iter().flatten().collect(),
+                weight,
+            });
+        }
+        self
+    }
+
+    /// Returns the state, or every problem found while building and by `State::validate`
+    pub fn build(mut self) -> Result<State, Vec<ValidationError>> {
+        if let Err(errors) = self.state.validate() {
+            self.errors.extend(errors);
+        }
+        if self.errors.is_empty() {
+            Ok(self.state)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
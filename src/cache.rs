@@ -0,0 +1,301 @@
+//! Reuse of solutions for states seen before, keyed by a hash that does not depend on the order
+//! of connections or on names. Each entry keeps the canonical encoding of its state and context,
+//! which must match on lookup. Entries live in memory (least recently used ones are evicted)
+//! and optionally as files in a directory shared between runs.
+
+use crate::solver::{HqSolution, check_solution};
+use crate::state::State;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::PathBuf;
+
+/// 64-bit FNV-1a, stable across runs unlike `DefaultHasher`
+fn fnv(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Numbers as u64 little-endian, so the encoding does not depend on `Hash` implementations or
+/// the platform
+#[derive(Default)]
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u64(&mut self, n: u64) {
+        self.0.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn usize(&mut self, n: usize) {
+        self.u64(n as u64);
+    }
+
+    fn index(&mut self, (r, g): (usize, usize)) {
+        self.usize(r);
+        self.usize(g);
+    }
+}
+
+/// Weights equal as numbers encode equally (0.0 and -0.0)
+fn weight_bits(weight: f64) -> u64 {
+    if weight == 0.0 { 0 } else { weight.to_bits() }
+}
+
+/// Free amounts, requests and connections of `state`, with connections, hyper connections,
+/// connection endpoints and hyper connection members sorted. Every list is preceded by its
+/// length, so different states never encode equally.
+fn canonical_bytes(state: &State) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    encoder.usize(state.groups.len());
+    for group in &state.groups {
+        encoder.u64(group.request.as_fractions());
+        encoder.usize(group.free.len());
+        for free in &group.free {
+            encoder.u64(free.as_fractions());
+        }
+    }
+    let mut connections: Vec<_> = state
+        .connections
+        .iter()
+        .map(|c| {
+            let (a, b) = ((c.r1, c.g1), (c.r2, c.g2));
+            (a.min(b), a.max(b), weight_bits(c.weight))
+        })
+        .collect();
+    connections.sort_unstable();
+    encoder.usize(connections.len());
+    for (a, b, weight) in connections {
+        encoder.index(a);
+        encoder.index(b);
+        encoder.u64(weight);
+    }
+    let mut hyper: Vec<_> = state
+        .hyper_connections
+        .iter()
+        .map(|h| {
+            let mut members = h.members.clone();
+            members.sort_unstable();
+            (members, weight_bits(h.weight))
+        })
+        .collect();
+    hyper.sort_unstable();
+    encoder.usize(hyper.len());
+    for (members, weight) in hyper {
+        encoder.usize(members.len());
+        for member in members {
+            encoder.index(member);
+        }
+        encoder.u64(weight);
+    }
+    encoder.0
+}
+
+/// Hash of the free amounts, requests and connections of `state`. States that only differ in
+/// the order of connections, hyper connections, connection endpoints or hyper connection
+/// members, or in group names and index labels, have the same hash.
+pub fn canonical_hash(state: &State) -> u64 {
+    fnv(&canonical_bytes(state))
+}
+
+/// Identifies a state solved in a context. Entries are found by `hash` and only used when
+/// `identity` matches too, so hash collisions are misses.
+#[derive(Debug, Clone)]
+pub struct CacheKey {
+    pub hash: u64,
+    identity: Vec<u8>,
+}
+
+/// Cache key of `state` solved in `context`, which describes everything else that affects the
+/// solution (backend, options)
+pub fn cache_key(state: &State, context: &str) -> CacheKey {
+    let mut encoder = Encoder(canonical_bytes(state));
+    encoder.usize(context.len());
+    encoder.0.extend_from_slice(context.as_bytes());
+    CacheKey {
+        hash: fnv(&encoder.0),
+        identity: encoder.0,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheStats {
+    pub hits: u64,
+    /// Hits found on disk but not in memory, included in `hits`
+    pub disk_hits: u64,
+    pub misses: u64,
+}
+
+struct Entry {
+    identity: Vec<u8>,
+    solution: Option<HqSolution>,
+    last_used: u64,
+}
+
+/// Content of a cache file; `identity` is the hex encoded `CacheKey::identity`
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    identity: String,
+    solution: Option<HqSolution>,
+}
+
+/// Solutions (or the lack of one) by cache key
+pub struct SolutionCache {
+    capacity: usize,
+    dir: Option<PathBuf>,
+    entries: HashMap<u64, Entry>,
+    clock: u64,
+    pub stats: CacheStats,
+}
+
+impl SolutionCache {
+    /// Keeps up to `capacity` entries in memory, and every entry as `<hash>.json` in `dir`
+    pub fn new(capacity: usize, dir: Option<PathBuf>) -> io::Result<Self> {
+        if let Some(dir) = &dir {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(SolutionCache {
+            capacity,
+            dir,
+            entries: HashMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        })
+    }
+
+    fn path(&self, key: u64) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(format!("{key:016x}.json")))
+    }
+
+    /// The cached result for `key`, if any. An entry of another state with the same hash, or a
+    /// cached solution that is not a valid allocation for `state` (an edited file), counts as a
+    /// miss.
+    pub fn get(&mut self, state: &State, key: &CacheKey) -> Option<Option<HqSolution>> {
+        self.clock += 1;
+        let found = match self.entries.get_mut(&key.hash) {
+            Some(entry) if entry.identity == key.identity => {
+                entry.last_used = self.clock;
+                Some((entry.solution.clone(), false))
+            }
+            Some(_) => None,
+            None => self
+                .path(key.hash)
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .and_then(|text| serde_json::from_str::<FileEntry>(&text).ok())
+                .filter(|entry| entry.identity == hex(&key.identity))
+                .map(|entry| (entry.solution, true)),
+        };
+        let valid =
+            |s: &Option<HqSolution>| s.as_ref().is_none_or(|s| check_solution(state, s).is_ok());
+        match found {
+            Some((solution, from_disk)) if valid(&solution) => {
+                self.stats.hits += 1;
+                if from_disk {
+                    self.stats.disk_hits += 1;
+                    self.remember(key.clone(), solution.clone());
+                }
+                Some(solution)
+            }
+            _ => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Stores a result in memory and on disk. Failing to write the file is ignored, the entry
+    /// is then only cached in memory.
+    pub fn insert(&mut self, key: CacheKey, solution: Option<HqSolution>) {
+        let entry = FileEntry {
+            identity: hex(&key.identity),
+            solution,
+        };
+        if let Some(path) = self.path(key.hash)
+            && let Ok(json) = serde_json::to_string(&entry)
+        {
+            let _ = std::fs::write(path, json);
+        }
+        self.remember(key, entry.solution);
+    }
+
+    fn remember(&mut self, key: CacheKey, solution: Option<HqSolution>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key.hash) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(hash, _)| *hash);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(
+            key.hash,
+            Entry {
+                identity: key.identity,
+                solution,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// The cached result for `state` in `context`, otherwise the result of `solve`, which is
+    /// then cached unless it is an error
+    pub fn get_or_solve<E>(
+        &mut self,
+        state: &State,
+        context: &str,
+        solve: impl FnOnce(&State) -> Result<Option<HqSolution>, E>,
+    ) -> Result<Option<HqSolution>, E> {
+        let key = cache_key(state, context);
+        if let Some(solution) = self.get(state, &key) {
+            return Ok(solution);
+        }
+        let solution = solve(state)?;
+        self.insert(key, solution.clone());
+        Ok(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmarks;
+
+    #[test]
+    fn collision_is_a_miss() {
+        let state = benchmarks::create("empty_4").unwrap();
+        let mut cache = SolutionCache::new(8, None).unwrap();
+        let key = cache_key(&state, "a");
+        cache.insert(key.clone(), None);
+        assert_eq!(cache.get(&state, &key).map(|s| s.is_none()), Some(true));
+        let forged = CacheKey {
+            hash: key.hash,
+            ..cache_key(&state, "b")
+        };
+        assert!(cache.get(&state, &forged).is_none());
+        assert_eq!((cache.stats.hits, cache.stats.misses), (1, 1));
+    }
+
+    #[test]
+    fn canonical_hash_ignores_order() {
+        let state = benchmarks::create("one2one_8_8_8").unwrap();
+        let mut reordered = state.clone();
+        reordered.connections.reverse();
+        assert_eq!(canonical_hash(&state), canonical_hash(&reordered));
+        reordered.connections.pop();
+        assert_ne!(canonical_hash(&state), canonical_hash(&reordered));
+    }
+}
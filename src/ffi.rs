@@ -0,0 +1,188 @@
+//! C interface for embedding the allocator, declared in `ffi/lptest.h`; `ffi/test.c` shows the
+//! usage. Regenerate the header after changing this module with
+//! `cbindgen --config ffi/cbindgen.toml --output ffi/lptest.h`.
+//!
+//! All pointers must be non-null and come from the matching `lp_*_new`/`lp_solve` call, and
+//! must not be used after being passed to the matching `lp_*_free`. Invalid groups and indices
+//! are reported by `LpStatus`; the state itself is only validated by `lp_solve`.
+#![allow(clippy::missing_safety_doc)]
+
+use crate::solver::{HqSolution, SolveOptions, solve};
+use crate::state::{Amount, Connection, Group, State};
+use std::panic;
+use std::ptr;
+
+/// Opaque handle of a `State`
+pub struct LpState(State);
+
+/// Opaque handle of an `HqSolution`
+pub struct LpSolution(HqSolution);
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub enum LpBackend {
+    Highs,
+    Microlp,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LpStatus {
+    Ok,
+    /// The requests cannot be satisfied
+    NoSolution,
+    /// The state does not pass `State::validate`
+    InvalidState,
+    /// A group or index out of range, or a connection of an index to itself
+    InvalidArgument,
+    /// The backend panicked; no solution is returned
+    Internal,
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn lp_state_new() -> *mut LpState {
+    Box::into_raw(Box::new(LpState(State::default())))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_state_free(state: *mut LpState) {
+    if !state.is_null() {
+        drop(unsafe { Box::from_raw(state) });
+    }
+}
+
+/// Appends an empty group and returns its position
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_state_add_group(state: *mut LpState) -> usize {
+    let state = unsafe { &mut (*state).0 };
+    state.groups.push(Group::default());
+    state.groups.len() - 1
+}
+
+/// Appends an index with the given free amount to `group`; its position is the number of
+/// indices added to the group before
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_state_add_free(
+    state: *mut LpState,
+    group: usize,
+    units: u32,
+    fractions: u32,
+) -> LpStatus {
+    let state = unsafe { &mut (*state).0 };
+    let Some(group) = state.groups.get_mut(group) else {
+        return LpStatus::InvalidArgument;
+    };
+    group.free.push(Amount::new(units, fractions));
+    LpStatus::Ok
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_state_set_request(
+    state: *mut LpState,
+    group: usize,
+    units: u32,
+    fractions: u32,
+) -> LpStatus {
+    let state = unsafe { &mut (*state).0 };
+    let Some(group) = state.groups.get_mut(group) else {
+        return LpStatus::InvalidArgument;
+    };
+    group.request = Amount::new(units, fractions);
+    LpStatus::Ok
+}
+
+/// Connects index `g1` of group `r1` with index `g2` of group `r2`; a weight of `-INFINITY`
+/// forbids selecting both
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_state_add_connection(
+    state: *mut LpState,
+    r1: usize,
+    g1: usize,
+    r2: usize,
+    g2: usize,
+    weight: f64,
+) -> LpStatus {
+    let state = unsafe { &mut (*state).0 };
+    let exists = |r: usize, g: usize| {
+        state
+            .groups
+            .get(r)
+            .is_some_and(|group| g < group.free.len())
+    };
+    if !exists(r1, g1) || !exists(r2, g2) || (r1, g1) == (r2, g2) {
+        return LpStatus::InvalidArgument;
+    }
+    state
+        .connections
+        .push(Connection::new(r1, g1, r2, g2, weight));
+    LpStatus::Ok
+}
+
+/// Solves the state with the default options; on `LpStatus::Ok`, `*solution` is set to a new
+/// solution, otherwise to null
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_solve(
+    state: *const LpState,
+    backend: LpBackend,
+    solution: *mut *mut LpSolution,
+) -> LpStatus {
+    let state = unsafe { &(*state).0 };
+    unsafe { *solution = ptr::null_mut() };
+    let options = SolveOptions::default();
+    // Unwinding into C is undefined, and aborting would end the host process
+    let result = panic::catch_unwind(|| match backend {
+        LpBackend::Highs => solve(state, good_lp::solvers::highs::highs, &options),
+        LpBackend::Microlp => solve(state, good_lp::solvers::microlp::microlp, &options),
+    });
+    match result {
+        Ok(Ok(Some(result))) => {
+            unsafe { *solution = Box::into_raw(Box::new(LpSolution(result))) };
+            LpStatus::Ok
+        }
+        Ok(Ok(None)) => LpStatus::NoSolution,
+        Ok(Err(_)) => LpStatus::InvalidState,
+        Err(_) => LpStatus::Internal,
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_solution_free(solution: *mut LpSolution) {
+    if !solution.is_null() {
+        drop(unsafe { Box::from_raw(solution) });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_solution_objective(solution: *const LpSolution) -> f64 {
+    unsafe { (*solution).0.objective }
+}
+
+/// Number of selected indices in `group` (0 for an invalid group)
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_solution_index_count(
+    solution: *const LpSolution,
+    group: usize,
+) -> usize {
+    let solution = unsafe { &(*solution).0 };
+    solution.groups.get(group).map_or(0, Vec::len)
+}
+
+/// Copies up to `len` selected indices of `group` in ascending order to `indices` and returns
+/// how many indices were selected in the group
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lp_solution_indices(
+    solution: *const LpSolution,
+    group: usize,
+    indices: *mut usize,
+    len: usize,
+) -> usize {
+    let solution = unsafe { &(*solution).0 };
+    let Some(selected) = solution.groups.get(group) else {
+        return 0;
+    };
+    let n = selected.len().min(len);
+    if n > 0 {
+        unsafe { ptr::copy_nonoverlapping(selected.as_ptr(), indices, n) };
+    }
+    selected.len()
+}
@@ -0,0 +1,327 @@
+//! Minimal HTTP/1.1 front end of `service`, on TCP or a Unix socket, one request per connection.
+//!
+//! - `POST /solve[?solver=<name>]` with a `State` body returns a `service::Response`
+//! - `GET /benchmarks` returns the benchmark names
+//! - `GET /metrics` returns solve counts and latency histograms per solver (Prometheus text)
+
+use crate::benchmarks;
+use crate::service::{self, Outcome, Response};
+use crate::solver::HqSolution;
+use crate::state::State;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+#[cfg(unix)]
+use std::{fs, os::unix::fs::FileTypeExt, os::unix::net::UnixListener, path::PathBuf};
+
+/// Upper bounds of the latency histogram buckets in seconds
+const BUCKETS: [f64; 9] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0];
+
+/// Largest accepted request body
+const MAX_BODY: usize = 64 << 20;
+
+/// Time a client may pause while sending its request
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wait after a failed accept, e.g. when out of file descriptors
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+/// `unix:<path>` (only on Unix) or a TCP address such as `127.0.0.1:8080`
+#[derive(Debug, Clone)]
+pub enum Listen {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some("") => Err("Empty unix socket path".to_string()),
+            #[cfg(unix)]
+            Some(path) => Ok(Listen::Unix(PathBuf::from(path))),
+            #[cfg(not(unix))]
+            Some(_) => Err("Unix sockets are not supported on this platform".to_string()),
+            None => Ok(Listen::Tcp(s.to_string())),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SolverMetrics {
+    /// Count per status of `Outcome`
+    statuses: BTreeMap<&'static str, u64>,
+    /// Count per bucket of `BUCKETS`, plus one for larger latencies
+    buckets: [u64; BUCKETS.len() + 1],
+    seconds: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Metrics {
+    solvers: Mutex<BTreeMap<String, SolverMetrics>>,
+}
+
+impl Metrics {
+    fn record(&self, solver: &str, response: &Response) {
+        let status = match response.outcome {
+            Outcome::Solved { .. } => "solved",
+            Outcome::NoSolution { .. } => "no_solution",
+            Outcome::Error { .. } => "error",
+            Outcome::Timeout => "timeout",
+        };
+        let seconds = response.time_ms / 1000.0;
+        let mut solvers = self.solvers.lock().unwrap();
+        let m = solvers.entry(solver.to_string()).or_default();
+        *m.statuses.entry(status).or_default() += 1;
+        let bucket = BUCKETS
+            .iter()
+            .position(|b| seconds <= *b)
+            .unwrap_or(BUCKETS.len());
+        m.buckets[bucket] += 1;
+        m.seconds += seconds;
+        m.count += 1;
+    }
+
+    fn render(&self) -> String {
+        let solvers = self.solvers.lock().unwrap();
+        let mut out = String::new();
+        out += "# TYPE lptest_solves_total counter\n";
+        for (solver, m) in solvers.iter() {
+            for (status, count) in &m.statuses {
+                let _ = writeln!(
+                    out,
+                    "lptest_solves_total{{solver=\"{solver}\",status=\"{status}\"}} {count}"
+                );
+            }
+        }
+        out += "# TYPE lptest_solve_seconds histogram\n";
+        for (solver, m) in solvers.iter() {
+            let mut cumulative = 0;
+            let bounds = BUCKETS.iter().map(|b| b.to_string());
+            for (le, count) in bounds.chain(["+Inf".to_string()]).zip(m.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "lptest_solve_seconds_bucket{{solver=\"{solver}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "lptest_solve_seconds_sum{{solver=\"{solver}\"}} {}",
+                m.seconds
+            );
+            let _ = writeln!(
+                out,
+                "lptest_solve_seconds_count{{solver=\"{solver}\"}} {}",
+                m.count
+            );
+        }
+        out
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: String,
+}
+
+fn read_request(stream: &mut impl Read) -> Result<HttpRequest, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("Empty request")?.to_string();
+    let target = parts.next().ok_or("Missing request target")?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|q| !q.is_empty())
+        .map(|q| {
+            let (k, v) = q.split_once('=').unwrap_or((q, ""));
+            (k.to_string(), v.to_string())
+        })
+        .collect();
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| e.to_string())?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| format!("Invalid Content-Length: {e}"))?;
+        }
+    }
+    if length > MAX_BODY {
+        return Err(format!("Body of {length} bytes is too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok(HttpRequest {
+        method,
+        path: path.to_string(),
+        query,
+        body: String::from_utf8(body).map_err(|e| e.to_string())?,
+    })
+}
+
+fn write_response(
+    stream: &mut impl Write,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn error_body(error: &str) -> String {
+    let response = Response {
+        id: serde_json::Value::Null,
+        outcome: Outcome::Error {
+            error: error.to_string(),
+        },
+        time_ms: 0.0,
+    };
+    serde_json::to_string(&response).unwrap()
+}
+
+struct Server<'a, F> {
+    solvers: &'a [&'a str],
+    default_solver: &'a str,
+    solve: F,
+    metrics: Metrics,
+}
+
+impl<F: Fn(&str, &State) -> Result<Option<HqSolution>, String> + Sync> Server<'_, F> {
+    fn handle(&self, stream: &mut (impl Read + Write)) -> io::Result<()> {
+        const JSON: &str = "application/json";
+        let request = match read_request(stream) {
+            Ok(request) => request,
+            Err(e) => return write_response(stream, "400 Bad Request", JSON, &error_body(&e)),
+        };
+        match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/solve") => {
+                let solver = request
+                    .query
+                    .iter()
+                    .find(|(k, _)| k == "solver")
+                    .map_or(self.default_solver, |(_, v)| v.as_str());
+                if !self.solvers.contains(&solver) {
+                    let error = format!("Unknown solver: {solver}");
+                    return write_response(stream, "400 Bad Request", JSON, &error_body(&error));
+                }
+                let response =
+                    service::handle_state(&request.body, |state| (self.solve)(solver, state));
+                self.metrics.record(solver, &response);
+                let status = match response.outcome {
+                    Outcome::Error { .. } => "400 Bad Request",
+                    _ => "200 OK",
+                };
+                let body = serde_json::to_string(&response).unwrap();
+                write_response(stream, status, JSON, &body)
+            }
+            ("GET", "/benchmarks") => {
+                let body = serde_json::to_string(benchmarks::NAMES).unwrap();
+                write_response(stream, "200 OK", JSON, &body)
+            }
+            ("GET", "/metrics") => {
+                write_response(stream, "200 OK", "text/plain", &self.metrics.render())
+            }
+            (_, "/solve" | "/benchmarks" | "/metrics") => {
+                let body = error_body("Method not allowed");
+                write_response(stream, "405 Method Not Allowed", JSON, &body)
+            }
+            _ => write_response(stream, "404 Not Found", JSON, &error_body("Not found")),
+        }
+    }
+}
+
+/// Handles every accepted connection on its own thread. A failed accept is logged and does not
+/// stop the server.
+fn accept<'scope, F, S>(
+    scope: &'scope thread::Scope<'scope, '_>,
+    server: &'scope Server<'_, F>,
+    incoming: impl Iterator<Item = io::Result<S>>,
+) where
+    F: Fn(&str, &State) -> Result<Option<HqSolution>, String> + Sync,
+    S: Read + Write + Send + 'scope,
+{
+    for stream in incoming {
+        match stream {
+            Ok(mut stream) => {
+                scope.spawn(move || server.handle(&mut stream));
+            }
+            Err(e) => {
+                eprintln!("Cannot accept connection: {e}");
+                thread::sleep(ACCEPT_RETRY);
+            }
+        }
+    }
+}
+
+/// Serves requests until the process ends, each connection on its own thread. `solve` is
+/// called with one of `solvers` (by default `default_solver`) and the state to solve.
+pub fn run(
+    listen: &Listen,
+    solvers: &[&str],
+    default_solver: &str,
+    solve: impl Fn(&str, &State) -> Result<Option<HqSolution>, String> + Sync,
+) -> io::Result<()> {
+    let server = Server {
+        solvers,
+        default_solver,
+        solve,
+        metrics: Metrics::default(),
+    };
+    let server = &server;
+    thread::scope(|scope| match listen {
+        Listen::Tcp(address) => {
+            let listener = TcpListener::bind(address)?;
+            let incoming = listener.incoming().map(|stream| {
+                let stream = stream?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                Ok(stream)
+            });
+            accept(scope, server, incoming);
+            Ok(())
+        }
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            // Left behind by an earlier run
+            if fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                fs::remove_file(path)?;
+            }
+            let listener = UnixListener::bind(path)?;
+            let incoming = listener.incoming().map(|stream| {
+                let stream = stream?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                Ok(stream)
+            });
+            accept(scope, server, incoming);
+            Ok(())
+        }
+    })
+}
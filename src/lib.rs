@@ -0,0 +1,19 @@
+//! Allocation of indices (cores, GPUs, ...) in resource groups as a MILP.
+//!
+//! A [`state::State`] describes the free amounts of every index, the requested amount per group
+//! and weighted connections between indices. [`solver::solve`] selects the indices for the
+//! requests and returns an [`solver::HqSolution`], which [`state::State::allocate`] applies.
+
+pub mod batch;
+pub mod benchmarks;
+pub mod builder;
+pub mod cache;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod replay;
+pub mod service;
+pub mod simulation;
+pub mod solver;
+pub mod state;
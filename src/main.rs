@@ -1,11 +1,28 @@
-mod solver;
-mod state;
-
-use crate::solver::{HqSolution, solve};
-use crate::state::{State, create_diamonds_32_16_32, create_empty_4, create_empty_8_8, create_empty_8_8_8, create_empty_16, create_empty_32, create_one2one_8_8_8, create_one2one_12_12, create_onlyo_4, create_onlyo_16, create_onlyo_32, create_primes_16, create_primes_16_n, create_random_18_18, create_stairs_32_16_8, create_triplets_16, create_fractions_16};
-use clap::{Parser, ValueEnum};
-use good_lp::{Expression, Solution, SolverModel, constraint, default_solver, variable, variables};
-use microlp::{ComparisonOp, OptimizationDirection, Problem};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use lptest::batch::{
+    BatchOptions, BatchSummary, CSV_HEADER, csv_field, instance_files, solve_batch,
+};
+use lptest::benchmarks;
+use lptest::cache::SolutionCache;
+#[cfg(feature = "http")]
+use lptest::cache::cache_key;
+#[cfg(feature = "http")]
+use lptest::http::{self, Listen};
+use lptest::replay::{EventStatus, parse_events, replay};
+use lptest::service;
+use lptest::simulation::{generate_trace, parse_trace, simulate};
+use lptest::solver::{
+    HqSolution, ObjectiveTerm, SolveOptions, affinity, diagnose, pareto_front, solve,
+    solve_and_allocate, solve_tasks, solve_top_k,
+};
+use lptest::state::{Amount, State, Task, ValidationError};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+#[cfg(feature = "http")]
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 #[derive(ValueEnum, Copy, Clone, Debug)]
 enum SolverType {
@@ -15,72 +32,899 @@ enum SolverType {
     Microlp,
 }
 
+#[derive(ValueEnum, Copy, Clone, Debug, Default)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    /// One row per group
+    Csv,
+}
+
+#[derive(Serialize)]
+struct GroupReport {
+    name: String,
+    indices: Vec<usize>,
+    labels: Vec<String>,
+    amounts: Vec<Amount>,
+}
+
+/// Result of a single solve for `--output json` and `--output csv`
+#[derive(Serialize)]
+struct SolveReport {
+    benchmark: String,
+    solver: String,
+    /// "solved" or "no_solution"
+    status: &'static str,
+    objective: Option<f64>,
+    groups: Vec<GroupReport>,
+    /// Why no solution was found, see `diagnose`
+    reasons: Vec<String>,
+    time_ms: f64,
+}
+
+impl SolveReport {
+    fn new(
+        benchmark: &str,
+        solver: SolverType,
+        state: &State,
+        solution: Option<&HqSolution>,
+        time: Duration,
+    ) -> Self {
+        let groups = solution.map_or(Vec::new(), |solution| {
+            solution
+                .groups
+                .iter()
+                .zip(&solution.amounts)
+                .enumerate()
+                .map(|(r, (indices, amounts))| GroupReport {
+                    name: state.group_name(r),
+                    indices: indices.clone(),
+                    labels: indices.iter().map(|&g| state.index_label(r, g)).collect(),
+                    amounts: amounts.clone(),
+                })
+                .collect()
+        });
+        let reasons = match solution {
+            Some(_) => Vec::new(),
+            None => diagnose(state).iter().map(|r| r.to_string()).collect(),
+        };
+        SolveReport {
+            benchmark: benchmark.to_string(),
+            solver: format!("{:?}", solver).to_lowercase(),
+            status: if solution.is_some() {
+                "solved"
+            } else {
+                "no_solution"
+            },
+            objective: solution.map(|s| s.objective),
+            groups,
+            reasons,
+            time_ms: time.as_secs_f64() * 1000.0,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "benchmark,solver,status,objective,time_ms,group,indices,labels,amounts\n",
+        );
+        let prefix = format!(
+            "{},{},{},{},{}",
+            csv_field(&self.benchmark),
+            self.solver,
+            self.status,
+            self.objective.map_or(String::new(), |o| o.to_string()),
+            self.time_ms
+        );
+        if self.groups.is_empty() {
+            csv += &format!("{},,,,\n", prefix);
+        }
+        for group in &self.groups {
+            let join = |items: Vec<String>| items.join(" ");
+            csv += &format!(
+                "{},{},{},{},{}\n",
+                prefix,
+                csv_field(&group.name),
+                join(group.indices.iter().map(|i| i.to_string()).collect()),
+                csv_field(&join(group.labels.clone())),
+                join(group.amounts.iter().map(|a| a.to_string()).collect()),
+            );
+        }
+        csv
+    }
+}
+
+#[derive(Args)]
+struct SolveArgs {
+    /// Add ordering constraints between interchangeable indices
+    #[clap(long)]
+    symmetry_breaking: bool,
+
+    /// Use one linking constraint per index for all its connections
+    #[clap(long)]
+    aggregate_connections: bool,
+
+    /// Reward for fully drained indices, shared so that values up to 1024 keep minimizing the
+    /// index count first
+    #[clap(long)]
+    fragmentation: Option<f64>,
+
+    /// Optimize these terms one after another instead of their weighted sum
+    /// (index-count, affinity, leftovers, fragmentation)
+    #[clap(long, value_delimiter = ',')]
+    lexicographic: Vec<ObjectiveTerm>,
+
+    /// Break ties between optimal solutions deterministically
+    #[clap(long)]
+    canonical: bool,
+}
+
+impl SolveArgs {
+    fn options(&self) -> SolveOptions {
+        SolveOptions {
+            symmetry_breaking: self.symmetry_breaking,
+            aggregate_connections: self.aggregate_connections,
+            fragmentation: self.fragmentation,
+            lexicographic: self.lexicographic.clone(),
+            canonical: self.canonical,
+        }
+    }
+}
+
 #[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Opts {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(required = true)]
+    solver: Option<SolverType>,
+    #[clap(required = true)]
+    benchmark: Option<String>,
+
+    #[clap(long)]
+    repeats: Option<usize>,
+
+    #[clap(flatten)]
+    solve: SolveArgs,
+
+    /// Allocate N copies of the benchmark request jointly, earlier copies have higher priority
+    #[clap(long)]
+    tasks: Option<usize>,
+
+    /// Allocate the benchmark request up to N times one after another on the same node,
+    /// then release all allocations again
+    #[clap(long)]
+    sequential: Option<usize>,
+
+    /// Enumerate the K best distinct selections
+    #[clap(long)]
+    top_k: Option<usize>,
+
+    /// Enumerate all optimal selections (up to --top-k if given)
+    #[clap(long)]
+    all_optimal: bool,
+
+    /// Format of the result of a single solve
+    #[clap(long, value_enum, default_value_t)]
+    output: OutputFormat,
+
+    #[clap(flatten)]
+    cache: CacheArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Simulate a node serving a stream of tasks
+    Simulate(SimulateOpts),
+    /// Replay a trace of requests and releases and validate every allocation
+    Replay(ReplayOpts),
+    /// List the Pareto-optimal solutions trading index count against affinity
+    Pareto(ParetoOpts),
+    /// Solve every `*.json` state in a directory on a pool of threads
+    Batch(BatchOpts),
+    /// Answer JSON lines `{"id": ..., "state": ...}` from stdin on stdout until stdin closes,
+    /// or HTTP requests with `--listen`
+    Serve(ServeOpts),
+}
+
+#[derive(Args)]
+struct CacheArgs {
+    /// Keep up to N solutions in memory and reuse them for equal states
+    #[clap(long, value_name = "N")]
+    cache: Option<usize>,
+
+    /// Also keep every solution as a file in this directory, reused across runs
+    #[clap(long, requires = "cache")]
+    cache_dir: Option<PathBuf>,
+}
+
+impl CacheArgs {
+    fn cache(&self) -> Result<Option<SolutionCache>, String> {
+        let Some(capacity) = self.cache else {
+            return Ok(None);
+        };
+        SolutionCache::new(capacity, self.cache_dir.clone())
+            .map(Some)
+            .map_err(|e| format!("Cannot create cache directory: {}", e))
+    }
+}
+
+#[derive(Args)]
+struct BatchOpts {
+    dir: PathBuf,
+
+    #[clap(long, default_value = "highs")]
+    solver: SolverType,
+
+    /// Worker threads [default: number of CPUs]
+    #[clap(long)]
+    threads: Option<usize>,
+
+    /// Time limit per instance in seconds
+    #[clap(long)]
+    timeout: Option<f64>,
+
+    /// Write each result as soon as it is known; CSV when the name ends with `.csv`,
+    /// JSON lines otherwise
+    #[clap(long)]
+    output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    solve: SolveArgs,
+}
+
+#[derive(Args)]
+struct ServeOpts {
+    /// Default solver, `POST /solve?solver=<name>` picks another one
+    #[clap(long, default_value = "highs")]
+    solver: SolverType,
+
+    #[clap(flatten)]
+    cache: CacheArgs,
+
+    /// Serve HTTP on `host:port` or `unix:<path>`: `POST /solve`, `GET /benchmarks`, `GET /metrics`
+    #[cfg(feature = "http")]
+    #[clap(long)]
+    listen: Option<Listen>,
+
+    #[clap(flatten)]
+    solve: SolveArgs,
+}
+
+#[derive(Args)]
+struct ParetoOpts {
     solver: SolverType,
     benchmark: String,
 
+    #[clap(flatten)]
+    solve: SolveArgs,
+
+    /// Also write the front as CSV to this file
     #[clap(long)]
-    repeats: Option<usize>,
+    csv: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ReplayOpts {
+    /// JSON lines: a topology snapshot followed by request and release events
+    file: PathBuf,
+
+    #[clap(long, default_value = "highs")]
+    solver: SolverType,
+
+    #[clap(flatten)]
+    solve: SolveArgs,
+}
+
+#[derive(Args)]
+struct SimulateOpts {
+    solver: SolverType,
+    /// Benchmark used as the empty node; its requests bound the generated task requests
+    benchmark: String,
+
+    #[clap(flatten)]
+    solve: SolveArgs,
+
+    /// Read tasks from a trace file instead of generating them
+    #[clap(long)]
+    trace: Option<PathBuf>,
+
+    /// Number of generated tasks
+    #[clap(long, default_value_t = 100)]
+    n_tasks: usize,
+
+    /// Mean time between arrivals of generated tasks
+    #[clap(long, default_value_t = 1.0)]
+    interarrival: f64,
+
+    /// Mean duration of generated tasks
+    #[clap(long, default_value_t = 10.0)]
+    duration: f64,
+
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Time between printed samples
+    #[clap(long, default_value_t = 10.0)]
+    sample_interval: f64,
+}
+
+fn validation_message(errors: &[ValidationError]) -> String {
+    let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    errors.join("; ")
+}
+
+fn run_solver(
+    state: &State,
+    solver_type: SolverType,
+    options: &SolveOptions,
+) -> Result<Option<HqSolution>, String> {
+    let solution = match solver_type {
+        SolverType::Highs => solve(state, good_lp::solvers::highs::highs, options),
+        SolverType::Microlp => solve(state, good_lp::solvers::microlp::microlp, options),
+    };
+    solution.map_err(|errors| validation_message(&errors))
 }
 
-fn run_solver(state: &State, solver_type: SolverType) -> Option<HqSolution> {
+/// Solves through `cache` when given; the cache key covers the solver and all options
+fn run_cached_solver(
+    cache: Option<&mut SolutionCache>,
+    state: &State,
+    solver_type: SolverType,
+    options: &SolveOptions,
+) -> Result<Option<HqSolution>, String> {
+    match cache {
+        Some(cache) => {
+            cache.get_or_solve(state, &format!("{solver_type:?} {options:?}"), |state| {
+                run_solver(state, solver_type, options)
+            })
+        }
+        None => run_solver(state, solver_type, options),
+    }
+}
+
+fn run_solve_and_allocate(
+    state: &mut State,
+    requests: &[Amount],
+    solver_type: SolverType,
+    options: &SolveOptions,
+) -> Result<Option<HqSolution>, String> {
     match solver_type {
-        SolverType::Highs => solve(state, good_lp::solvers::highs::highs),
-        SolverType::Microlp => solve(state, good_lp::solvers::microlp::microlp),
+        SolverType::Highs => {
+            solve_and_allocate(state, requests, good_lp::solvers::highs::highs, options)
+        }
+        SolverType::Microlp => {
+            solve_and_allocate(state, requests, good_lp::solvers::microlp::microlp, options)
+        }
+    }
+}
+
+fn run_tasks_solver(
+    state: &State,
+    tasks: &[Task],
+    solver_type: SolverType,
+    options: &SolveOptions,
+) -> Result<Option<Vec<Option<HqSolution>>>, String> {
+    let solutions = match solver_type {
+        SolverType::Highs => solve_tasks(state, tasks, good_lp::solvers::highs::highs, options),
+        SolverType::Microlp => {
+            solve_tasks(state, tasks, good_lp::solvers::microlp::microlp, options)
+        }
+    };
+    solutions.map_err(|errors| validation_message(&errors))
+}
+
+fn run_top_k(
+    state: &State,
+    solver_type: SolverType,
+    options: &SolveOptions,
+    k: usize,
+    optimal_only: bool,
+) -> Result<Vec<HqSolution>, String> {
+    let solutions = match solver_type {
+        SolverType::Highs => solve_top_k(
+            state,
+            good_lp::solvers::highs::highs,
+            options,
+            k,
+            optimal_only,
+        ),
+        SolverType::Microlp => solve_top_k(
+            state,
+            good_lp::solvers::microlp::microlp,
+            options,
+            k,
+            optimal_only,
+        ),
+    };
+    solutions.map_err(|errors| validation_message(&errors))
+}
+
+fn run_pareto_front(
+    state: &State,
+    solver_type: SolverType,
+    options: &SolveOptions,
+) -> Result<Vec<HqSolution>, String> {
+    let front = match solver_type {
+        SolverType::Highs => pareto_front(state, good_lp::solvers::highs::highs, options),
+        SolverType::Microlp => pareto_front(state, good_lp::solvers::microlp::microlp, options),
+    };
+    front.map_err(|errors| validation_message(&errors))
+}
+
+/// Selected indices by group name and index label, e.g. `{gpus: [gpu0, gpu3], 1: [2]}`
+fn format_selection(state: &State, groups: &[Vec<usize>]) -> String {
+    let groups: Vec<String> = groups
+        .iter()
+        .enumerate()
+        .map(|(r, indices)| {
+            let labels: Vec<String> = indices.iter().map(|&g| state.index_label(r, g)).collect();
+            format!("{}: [{}]", state.group_name(r), labels.join(", "))
+        })
+        .collect();
+    format!("{{{}}}", groups.join(", "))
+}
+
+fn format_amounts(amounts: &[Vec<Amount>]) -> String {
+    let groups: Vec<String> = amounts
+        .iter()
+        .map(|a| {
+            let a: Vec<String> = a.iter().map(|a| a.to_string()).collect();
+            format!("[{}]", a.join(", "))
+        })
+        .collect();
+    format!("[{}]", groups.join(", "))
+}
+
+fn run_simulation(opts: &SimulateOpts) -> Result<(), String> {
+    let positive = [
+        ("--sample-interval", opts.sample_interval),
+        ("--interarrival", opts.interarrival),
+        ("--duration", opts.duration),
+    ];
+    for (name, value) in positive {
+        // A sample interval of zero would never advance the sampling loop
+        if value.is_nan() || value <= 0.0 {
+            return Err(format!("{name} must be positive, got {value}"));
+        }
+    }
+    let state = benchmarks::create(&opts.benchmark)?;
+    let trace = match &opts.trace {
+        Some(path) => parse_trace(
+            &std::fs::read_to_string(path)
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?,
+        )?,
+        None => generate_trace(
+            &state,
+            opts.n_tasks,
+            opts.interarrival,
+            opts.duration,
+            opts.seed,
+        ),
+    };
+    if let Some(task) = trace
+        .iter()
+        .find(|t| t.requests.len() != state.groups.len())
+    {
+        return Err(format!(
+            "Task arriving at {} has {} requests, node has {} groups",
+            task.arrival,
+            task.requests.len(),
+            state.groups.len()
+        ));
     }
+    let n_groups = state.groups.len();
+    let options = opts.solve.options();
+    let report = simulate(state, &trace, opts.sample_interval, |state| {
+        run_solver(state, opts.solver, &options)
+    })?;
+
+    let groups: String = (0..n_groups)
+        .map(|r| format!(" util{:<3} frag{:<3}", r, r))
+        .collect();
+    println!("{:>10} {:>7} {:>6}{}", "time", "running", "queued", groups);
+    for sample in &report.samples {
+        let groups: String = sample
+            .utilization
+            .iter()
+            .zip(&sample.fragmentation)
+            .map(|(u, f)| format!(" {:>6.1}% {:>6.1}%", u * 100.0, f * 100.0))
+            .collect();
+        println!(
+            "{:>10.1} {:>7} {:>6}{}",
+            sample.time, sample.running, sample.queued, groups
+        );
+    }
+
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len().max(1) as f64;
+    let allocated = report.waits.len();
+    println!("Tasks: {}", trace.len());
+    println!("Finished: {}", report.finished);
+    println!("Rejected: {}", report.rejected);
+    println!(
+        "Started immediately: {} ({:.1}%)",
+        report.started_immediately,
+        report.started_immediately as f64 * 100.0 / allocated.max(1) as f64
+    );
+    println!(
+        "Wait time: mean {:.3}, max {:.3}",
+        mean(&report.waits),
+        report.waits.iter().copied().fold(0.0, f64::max)
+    );
+    for r in 0..n_groups {
+        let fragmentation: Vec<f64> = report.samples.iter().map(|s| s.fragmentation[r]).collect();
+        println!(
+            "Group {}: mean utilization {:.1}%, mean fragmentation {:.1}%",
+            r,
+            report.mean_utilization[r] * 100.0,
+            mean(&fragmentation) * 100.0
+        );
+    }
+    println!(
+        "Affinity: mean {:.3} per task, total {:.3}",
+        mean(&report.affinities),
+        report.affinities.iter().sum::<f64>()
+    );
+    println!("End time: {:.3}", report.end_time);
+    Ok(())
+}
+
+fn run_pareto(opts: &ParetoOpts) -> Result<(), String> {
+    let state = benchmarks::create(&opts.benchmark)?;
+    let front = run_pareto_front(&state, opts.solver, &opts.solve.options())?;
+    if front.is_empty() {
+        println!("No solution found");
+        return Ok(());
+    }
+
+    println!(
+        "{:>6} {:>8} {:>12} {:>14}  groups",
+        "point", "indices", "affinity", "objective"
+    );
+    let mut csv = String::from("indices,affinity,objective,groups\n");
+    for (n, solution) in front.iter().enumerate() {
+        let indices: usize = solution.groups.iter().map(|g| g.len()).sum();
+        let affinity = affinity(&state, solution);
+        let groups = if state.has_names() {
+            format_selection(&state, &solution.groups)
+        } else {
+            format!("{:?}", solution.groups)
+        };
+        println!(
+            "{:>6} {:>8} {:>12.3} {:>14.3}  {}",
+            n, indices, affinity, solution.objective, groups
+        );
+        csv += &format!(
+            "{},{},{},\"{:?}\"\n",
+            indices, affinity, solution.objective, solution.groups
+        );
+    }
+    if let Some(path) = &opts.csv {
+        std::fs::write(path, csv).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "http")]
+fn run_http(opts: &ServeOpts, listen: &Listen, cache: Option<SolutionCache>) -> Result<(), String> {
+    let options = opts.solve.options();
+    let cache = cache.map(Mutex::new);
+    let names: Vec<String> = SolverType::value_variants()
+        .iter()
+        .filter_map(|s| s.to_possible_value())
+        .map(|v| v.get_name().to_string())
+        .collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let default = opts.solver.to_possible_value().unwrap();
+    http::run(listen, &names, default.get_name(), |name, state| {
+        let solver = SolverType::from_str(name, false)?;
+        let Some(cache) = &cache else {
+            return run_solver(state, solver, &options);
+        };
+        // Not locked while solving, so other requests are served meanwhile
+        let key = cache_key(state, &format!("{solver:?} {options:?}"));
+        if let Some(solution) = cache.lock().unwrap().get(state, &key) {
+            return Ok(solution);
+        }
+        let solution = run_solver(state, solver, &options)?;
+        cache.lock().unwrap().insert(key, solution.clone());
+        Ok(solution)
+    })
+    .map_err(|e| e.to_string())
+}
+
+fn run_serve(opts: &ServeOpts) -> Result<(), String> {
+    let options = opts.solve.options();
+    let mut cache = opts.cache.cache()?;
+    #[cfg(feature = "http")]
+    if let Some(listen) = &opts.listen {
+        return run_http(opts, listen, cache);
+    }
+    service::serve(std::io::stdin().lock(), std::io::stdout().lock(), |state| {
+        run_cached_solver(cache.as_mut(), state, opts.solver, &options)
+    })
+    .map_err(|e| e.to_string())
+}
+
+fn run_batch(opts: &BatchOpts) -> Result<(), String> {
+    let files = instance_files(&opts.dir)
+        .map_err(|e| format!("Cannot read {}: {}", opts.dir.display(), e))?;
+    let threads = opts
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let timeout = opts
+        .timeout
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|e| format!("Invalid timeout: {}", e))?;
+    let csv = opts
+        .output
+        .as_ref()
+        .is_some_and(|p| p.extension().is_some_and(|e| e == "csv"));
+    let mut output = match &opts.output {
+        Some(path) => {
+            Some(BufWriter::new(File::create(path).map_err(|e| {
+                format!("Cannot write {}: {}", path.display(), e)
+            })?))
+        }
+        None => None,
+    };
+    let mut write = |line: &str| -> std::io::Result<()> {
+        if let Some(output) = &mut output {
+            writeln!(output, "{}", line)?;
+            output.flush()?;
+        }
+        Ok(())
+    };
+    if csv {
+        write(CSV_HEADER).map_err(|e| e.to_string())?;
+    }
+
+    let solver = opts.solver;
+    let options = opts.solve.options();
+    let start = Instant::now();
+    let mut summary = BatchSummary::default();
+    let mut error = None;
+    solve_batch(
+        &files,
+        &BatchOptions { threads, timeout },
+        move |state| run_solver(state, solver, &options),
+        |result| {
+            summary.add(&result);
+            let line = if csv {
+                result.to_csv()
+            } else {
+                serde_json::to_string(&result).unwrap()
+            };
+            if let Err(e) = write(&line) {
+                error.get_or_insert(e);
+            }
+        },
+    );
+    if let Some(e) = error {
+        return Err(format!("Cannot write results: {}", e));
+    }
+
+    println!("Instances: {}", summary.total());
+    println!("Solved: {}", summary.solved);
+    println!("No solution: {}", summary.no_solution);
+    println!("Errors: {}", summary.errors);
+    println!("Timeouts: {}", summary.timeouts);
+    let times = &summary.times_ms;
+    if !times.is_empty() {
+        println!(
+            "Solve time: mean {:.3} ms, max {:.3} ms",
+            times.iter().sum::<f64>() / times.len() as f64,
+            times.iter().copied().fold(0.0, f64::max)
+        );
+    }
+    println!("Threads: {}", threads);
+    println!("Wall time: {:.3} s", start.elapsed().as_secs_f64());
+    Ok(())
+}
+
+fn run_replay(opts: &ReplayOpts) -> Result<(), String> {
+    let text = std::fs::read_to_string(&opts.file)
+        .map_err(|e| format!("Cannot read {}: {}", opts.file.display(), e))?;
+    let events = parse_events(&text)?;
+    let options = opts.solve.options();
+    let results = replay(&events, |state| run_solver(state, opts.solver, &options));
+
+    println!(
+        "{:>6} {:<12} {:<10} {:>12} {:>14}",
+        "event", "id", "status", "latency[ms]", "objective"
+    );
+    let mut failures = 0;
+    let mut latencies = Vec::new();
+    for (n, result) in results.iter().enumerate() {
+        let (status, detail) = match &result.status {
+            EventStatus::Topology => ("topology", None),
+            EventStatus::Allocated => ("allocated", None),
+            EventStatus::Released => ("released", None),
+            EventStatus::NoSolution(reasons) => {
+                let reasons: Vec<String> = reasons.iter().map(|r| r.to_string()).collect();
+                ("no-solution", Some(reasons.join("; ")))
+            }
+            EventStatus::Invalid(e) => ("INVALID", Some(e.clone())),
+            EventStatus::Error(e) => ("ERROR", Some(e.clone())),
+        };
+        if matches!(
+            result.status,
+            EventStatus::Invalid(_) | EventStatus::Error(_)
+        ) {
+            failures += 1;
+        }
+        let latency = result.solve_time.map(|t| t.as_secs_f64() * 1000.0);
+        latencies.extend(latency);
+        println!(
+            "{:>6} {:<12} {:<10} {:>12} {:>14}{}",
+            n,
+            result.id.as_deref().unwrap_or("-"),
+            status,
+            latency.map_or("-".to_string(), |l| format!("{:.3}", l)),
+            result
+                .objective
+                .map_or("-".to_string(), |o| format!("{:.3}", o)),
+            detail.map_or(String::new(), |e| format!("  {}", e))
+        );
+    }
+    println!("Events: {}", results.len());
+    println!("Solves: {}", latencies.len());
+    if !latencies.is_empty() {
+        println!(
+            "Latency: mean {:.3} ms, max {:.3} ms",
+            latencies.iter().sum::<f64>() / latencies.len() as f64,
+            latencies.iter().copied().fold(0.0, f64::max)
+        );
+    }
+    println!("Invalid or failed events: {}", failures);
+    if failures > 0 {
+        return Err(format!("{} events failed", failures));
+    }
+    Ok(())
 }
 
 fn main() -> Result<(), String> {
     let opts = Opts::parse();
-    let state = match opts.benchmark.as_str() {
-        "empty_4" => create_empty_4(),
-        "empty_16" => create_empty_16(),
-        "empty_32" => create_empty_32(),
-        "empty_8_8" => create_empty_8_8(),
-        "empty_8_8_8" => create_empty_8_8_8(),
-        "onlyo_4" => create_onlyo_4(),
-        "onlyo_16" => create_onlyo_16(),
-        "onlyo_32" => create_onlyo_32(),
-        "primes_16" => create_primes_16(),
-        "primes_16_n" => create_primes_16_n(),
-        "triplets_16" => create_triplets_16(),
-        "one2one_12_12" => create_one2one_12_12(),
-        "one2one_8_8_8" => create_one2one_8_8_8(),
-        "all2all_16_16" => create_empty_16(),
-        "stairs_32_16_8" => create_stairs_32_16_8(),
-        "diamonds_16_32_16" => create_diamonds_32_16_32(),
-        "random_18_18_v1" => create_random_18_18(1234, 0.5),
-        "random_18_18_v2" => create_random_18_18(4242424242, 0.2),
-        "random_18_18_v3" => create_random_18_18(8888881, 0.8),
-        "empty_f_16" => create_empty_16(),
-        "fractions_16" => create_fractions_16(),
-        "random_f_18_18_v1" => create_random_18_18(22221, 0.20),
-        "random_f_18_18_v2" => create_random_18_18(33331, 0.60),
-        _ => return Err(format!("Invalid benchmark name: {}", opts.benchmark)),
-    };
+    match &opts.command {
+        Some(Command::Simulate(sim)) => return run_simulation(sim),
+        Some(Command::Replay(replay)) => return run_replay(replay),
+        Some(Command::Pareto(pareto)) => return run_pareto(pareto),
+        Some(Command::Batch(batch)) => return run_batch(batch),
+        Some(Command::Serve(serve)) => return run_serve(serve),
+        None => {}
+    }
+    let solver = opts.solver.unwrap();
+    let state = benchmarks::create(opts.benchmark.as_deref().unwrap())?;
+
+    let options = opts.solve.options();
+    let mut cache = opts.cache.cache()?;
+
+    if let Some(steps) = opts.sequential {
+        let mut state = state;
+        let requests: Vec<Amount> = state.groups.iter().map(|g| g.request).collect();
+        let mut allocated = Vec::new();
+        for i in 0..steps {
+            match run_solve_and_allocate(&mut state, &requests, solver, &options)? {
+                Some(solution) => {
+                    println!("Step {}: Groups: {:?}", i, solution.groups);
+                    if state.has_names() {
+                        let selection = format_selection(&state, &solution.groups);
+                        println!("Step {}: Selected: {}", i, selection);
+                    }
+                    println!("Step {}: Amounts: {}", i, format_amounts(&solution.amounts));
+                    allocated.push(solution);
+                }
+                None => {
+                    println!("Step {}: No solution found", i);
+                    for reason in diagnose(&state) {
+                        println!("Step {}:   {}", i, reason);
+                    }
+                    break;
+                }
+            }
+        }
+        for solution in allocated.iter().rev() {
+            state.release(solution)?;
+        }
+        let free: Vec<_> = state.groups.iter().map(|g| g.free.clone()).collect();
+        println!("Free after release: {}", format_amounts(&free));
+        return Ok(());
+    }
+
+    if let Some(n_tasks) = opts.tasks {
+        let requests: Vec<Amount> = state.groups.iter().map(|g| g.request).collect();
+        let tasks: Vec<Task> = (0..n_tasks)
+            .map(|i| Task {
+                requests: requests.clone(),
+                priority: (n_tasks - i) as f64,
+            })
+            .collect();
+        match run_tasks_solver(&state, &tasks, solver, &options)? {
+            Some(solutions) => {
+                for (i, solution) in solutions.iter().enumerate() {
+                    match solution {
+                        Some(solution) => {
+                            println!("Task {}: Solution: {}", i, solution.objective);
+                            println!("Task {}: Groups: {:?}", i, solution.groups);
+                            if state.has_names() {
+                                let selection = format_selection(&state, &solution.groups);
+                                println!("Task {}: Selected: {}", i, selection);
+                            }
+                            println!("Task {}: Amounts: {}", i, format_amounts(&solution.amounts));
+                        }
+                        None => println!("Task {}: Not allocated", i),
+                    }
+                }
+            }
+            None => println!("No solution found"),
+        }
+        return Ok(());
+    }
+
+    if opts.top_k.is_some() || opts.all_optimal {
+        let k = opts.top_k.unwrap_or(usize::MAX);
+        let solutions = run_top_k(&state, solver, &options, k, opts.all_optimal)?;
+        if solutions.is_empty() {
+            println!("No solution found");
+        }
+        for (i, solution) in solutions.iter().enumerate() {
+            println!("Solution {}: {}", i, solution.objective);
+            println!("Solution {}: Groups: {:?}", i, solution.groups);
+            if state.has_names() {
+                let selection = format_selection(&state, &solution.groups);
+                println!("Solution {}: Selected: {}", i, selection);
+            }
+        }
+        return Ok(());
+    }
 
     if let Some(repeats) = opts.repeats {
         // If repeats are enabled, just print dummy value to avoid compiler optimizing away the loop
         let mut dummy: f64 = 0.0;
         for _ in 0..repeats {
-            let solution = run_solver(&state, opts.solver);
+            let solution = run_cached_solver(cache.as_mut(), &state, solver, &options)?;
             if let Some(solution) = solution {
                 dummy += solution.objective;
             }
         }
         println!("dummy {}", dummy);
+        if let Some(cache) = &cache {
+            let stats = cache.stats;
+            println!(
+                "Cache: {} hits ({} from disk), {} misses",
+                stats.hits, stats.disk_hits, stats.misses
+            );
+        }
     } else {
         // If not repeats, run the solver and print the result
-        let solution = run_solver(&state, opts.solver);
-        match solution {
-            Some(solution) => {
-                println!("Solution: {}", solution.objective);
-                println!("Groups: {:?}", solution.groups);
-            }
-            None => {
-                println!("No solution found");
+        let start = Instant::now();
+        let solution = run_cached_solver(cache.as_mut(), &state, solver, &options)?;
+        let time = start.elapsed();
+        let benchmark = opts.benchmark.as_deref().unwrap();
+        let report = || SolveReport::new(benchmark, solver, &state, solution.as_ref(), time);
+        match opts.output {
+            OutputFormat::Text => match &solution {
+                Some(solution) => {
+                    println!("Solution: {}", solution.objective);
+                    println!("Groups: {:?}", solution.groups);
+                    if state.has_names() {
+                        println!("Selected: {}", format_selection(&state, &solution.groups));
+                    }
+                }
+                None => {
+                    println!("No solution found");
+                    for reason in diagnose(&state) {
+                        println!("  {}", reason);
+                    }
+                }
+            },
+            OutputFormat::Json => {
+                let json = serde_json::to_string(&report()).map_err(|e| e.to_string())?;
+                println!("{}", json);
             }
+            OutputFormat::Csv => print!("{}", report().to_csv()),
         }
     }
     Ok(())
@@ -0,0 +1,174 @@
+use crate::solver::{HqSolution, Infeasibility, check_solution, diagnose};
+use crate::state::{Amount, State};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// One line of a replay trace. The first event has to be a topology snapshot; a later snapshot
+/// replaces the node and drops all running allocations.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Topology(State),
+    Request { id: String, requests: Vec<Amount> },
+    Release { id: String },
+}
+
+pub fn parse_events(text: &str) -> Result<Vec<Event>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            let event =
+                serde_json::from_str(line).map_err(|e| format!("Trace line {}: {}", n + 1, e))?;
+            if let Event::Topology(state) = &event {
+                state.validate().map_err(|errors| {
+                    let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                    format!("Trace line {}: {}", n + 1, errors.join("; "))
+                })?;
+            }
+            Ok(event)
+        })
+        .collect()
+}
+
+pub enum EventStatus {
+    Topology,
+    Allocated,
+    Released,
+    /// No allocation exists, with the reasons found by `diagnose`
+    NoSolution(Vec<Infeasibility>),
+    /// Solver returned an allocation rejected by the validator
+    Invalid(String),
+    Error(String),
+}
+
+pub struct EventResult {
+    pub id: Option<String>,
+    pub status: EventStatus,
+    pub solve_time: Option<Duration>,
+    pub objective: Option<f64>,
+}
+
+/// Feeds the events through `solve`, checks every returned allocation with `check_solution`
+/// and applies valid allocations to the node until they are released. An error of `solve`
+/// (e.g. an invalid request) is reported as `EventStatus::Error`.
+pub fn replay(
+    events: &[Event],
+    mut solve: impl FnMut(&State) -> Result<Option<HqSolution>, String>,
+) -> Vec<EventResult> {
+    let mut state: Option<State> = None;
+    let mut running: HashMap<&str, HqSolution> = HashMap::new();
+    events
+        .iter()
+        .map(|event| {
+            let mut result = EventResult {
+                id: None,
+                status: EventStatus::Topology,
+                solve_time: None,
+                objective: None,
+            };
+            match event {
+                Event::Topology(topology) => {
+                    state = Some(topology.clone());
+                    running.clear();
+                }
+                Event::Request { id, requests } => {
+                    result.id = Some(id.clone());
+                    result.status = match state.as_mut() {
+                        None => EventStatus::Error("No topology before request".to_string()),
+                        Some(_) if running.contains_key(id.as_str()) => {
+                            EventStatus::Error(format!("Task {id} is already running"))
+                        }
+                        Some(state) if requests.len() != state.groups.len() => {
+                            EventStatus::Error(format!(
+                                "{} requests for {} groups",
+                                requests.len(),
+                                state.groups.len()
+                            ))
+                        }
+                        Some(state) => {
+                            state.set_requests(requests);
+                            let start = Instant::now();
+                            let solution = solve(state);
+                            result.solve_time = Some(start.elapsed());
+                            match solution {
+                                Err(e) => EventStatus::Error(e),
+                                Ok(None) => EventStatus::NoSolution(diagnose(state)),
+                                Ok(Some(solution)) => {
+                                    result.objective = Some(solution.objective);
+                                    match check_solution(state, &solution)
+                                        .and_then(|_| state.allocate(&solution))
+                                    {
+                                        Ok(()) => {
+                                            running.insert(id, solution);
+                                            EventStatus::Allocated
+                                        }
+                                        Err(e) => EventStatus::Invalid(e),
+                                    }
+                                }
+                            }
+                        }
+                    };
+                }
+                Event::Release { id } => {
+                    result.id = Some(id.clone());
+                    result.status = match (state.as_mut(), running.remove(id.as_str())) {
+                        (Some(state), Some(solution)) => match state.release(&solution) {
+                            Ok(()) => EventStatus::Released,
+                            Err(e) => EventStatus::Error(e),
+                        },
+                        _ => EventStatus::Error(format!("Task {id} is not running")),
+                    };
+                }
+            }
+            result
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{self, SolveOptions};
+    use good_lp::solvers::microlp::microlp;
+
+    #[test]
+    fn replay_reports_errors() {
+        let one = r#"{"units": 1, "fractions": 0}"#;
+        let events = parse_events(&format!(
+            r#"{{"type": "topology", "groups": [{{"free": [{one}, {one}]}}]}}
+            {{"type": "request", "id": "a", "requests": [{one}]}}
+            {{"type": "request", "id": "a", "requests": [{one}]}}
+            {{"type": "release", "id": "b"}}
+            {{"type": "release", "id": "a"}}
+            {{"type": "release", "id": "a"}}"#
+        ))
+        .unwrap();
+        let results = replay(&events, |state| {
+            solver::solve(state, microlp, &SolveOptions::default())
+                .map_err(|errors| format!("{errors:?}"))
+        });
+        let statuses: Vec<String> = results
+            .iter()
+            .map(|r| match &r.status {
+                EventStatus::Topology => "topology".to_string(),
+                EventStatus::Allocated => "allocated".to_string(),
+                EventStatus::Released => "released".to_string(),
+                EventStatus::Error(e) => e.clone(),
+                EventStatus::NoSolution(_) | EventStatus::Invalid(_) => "unexpected".to_string(),
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                "topology",
+                "allocated",
+                "Task a is already running",
+                "Task b is not running",
+                "released",
+                "Task a is not running",
+            ]
+        );
+    }
+}
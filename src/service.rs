@@ -0,0 +1,129 @@
+//! Solving requests given as JSON, one per line, for use as a long-running subprocess.
+
+use crate::solver::{HqSolution, diagnose};
+use crate::state::State;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
+
+/// `{"id": <any JSON>, "state": <State>}`; the id is echoed in the response
+#[derive(Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Value,
+    pub state: State,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Solved {
+        solution: HqSolution,
+    },
+    /// The requests cannot be satisfied, with the reasons found by `diagnose`
+    NoSolution {
+        reasons: Vec<String>,
+    },
+    /// Malformed request or invalid state
+    Error {
+        error: String,
+    },
+    /// The solve did not finish within the time limit of a batch
+    Timeout,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+    pub id: Value,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+    pub time_ms: f64,
+}
+
+/// Answers one request line. The id is echoed even when the state cannot be parsed, as long as
+/// the line is a JSON object.
+pub fn handle(
+    line: &str,
+    solve: impl FnOnce(&State) -> Result<Option<HqSolution>, String>,
+) -> Response {
+    let start = Instant::now();
+    let mut id = Value::Null;
+    let outcome = match serde_json::from_str::<Value>(line) {
+        Err(e) => Outcome::Error {
+            error: e.to_string(),
+        },
+        Ok(value) => {
+            id = value.get("id").cloned().unwrap_or(Value::Null);
+            match serde_json::from_value::<Request>(value) {
+                Err(e) => Outcome::Error {
+                    error: e.to_string(),
+                },
+                Ok(request) => solve_request(&request.state, solve),
+            }
+        }
+    };
+    respond(id, start, outcome)
+}
+
+/// Answers a request given as a bare `State`, without id
+pub fn handle_state(
+    text: &str,
+    solve: impl FnOnce(&State) -> Result<Option<HqSolution>, String>,
+) -> Response {
+    let start = Instant::now();
+    let outcome = match serde_json::from_str::<State>(text) {
+        Err(e) => Outcome::Error {
+            error: e.to_string(),
+        },
+        Ok(state) => solve_request(&state, solve),
+    };
+    respond(Value::Null, start, outcome)
+}
+
+fn respond(id: Value, start: Instant, outcome: Outcome) -> Response {
+    Response {
+        id,
+        outcome,
+        time_ms: start.elapsed().as_secs_f64() * 1000.0,
+    }
+}
+
+pub(crate) fn solve_request(
+    state: &State,
+    solve: impl FnOnce(&State) -> Result<Option<HqSolution>, String>,
+) -> Outcome {
+    if let Err(errors) = state.validate() {
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        return Outcome::Error {
+            error: errors.join("; "),
+        };
+    }
+    match solve(state) {
+        Ok(Some(solution)) => Outcome::Solved { solution },
+        Ok(None) => Outcome::NoSolution {
+            reasons: diagnose(state).iter().map(|r| r.to_string()).collect(),
+        },
+        Err(error) => Outcome::Error { error },
+    }
+}
+
+/// Reads requests line by line until the input ends and writes one response line for each,
+/// flushed immediately. Empty lines are skipped.
+pub fn serve(
+    input: impl BufRead,
+    mut output: impl Write,
+    mut solve: impl FnMut(&State) -> Result<Option<HqSolution>, String>,
+) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle(&line, &mut solve);
+        serde_json::to_writer(&mut output, &response)?;
+        writeln!(output)?;
+        output.flush()?;
+    }
+    Ok(())
+}
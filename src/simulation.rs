@@ -0,0 +1,283 @@
+use crate::solver::{HqSolution, affinity};
+use crate::state::{Amount, State};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+
+pub struct TraceTask {
+    pub arrival: f64,
+    pub duration: f64,
+    pub requests: Vec<Amount>,
+}
+
+/// Tasks arrive as a Poisson process and run for exponentially distributed durations.
+/// Each task asks for 1..=request.units units of every group with a non-zero request in `state`
+/// (plus its fractional request).
+pub fn generate_trace(
+    state: &State,
+    n_tasks: usize,
+    mean_interarrival: f64,
+    mean_duration: f64,
+    seed: u64,
+) -> Vec<TraceTask> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut exp = |mean: f64| -mean * (1.0 - rng.random::<f64>()).ln();
+    let mut time = 0.0;
+    let mut times = Vec::with_capacity(n_tasks);
+    for _ in 0..n_tasks {
+        time += exp(mean_interarrival);
+        times.push((time, exp(mean_duration)));
+    }
+    times
+        .into_iter()
+        .map(|(arrival, duration)| TraceTask {
+            arrival,
+            duration,
+            requests: state
+                .groups
+                .iter()
+                .map(|g| {
+                    let units = if g.request.units > 0 {
+                        rng.random_range(1..=g.request.units)
+                    } else {
+                        0
+                    };
+                    Amount::new(units, g.request.fractions)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Reads a trace with one task per line: `<arrival> <duration> <request of group 0> ...`,
+/// where a request is written as `units` or `units.fractions` (e.g. `2.5`). Arrivals must be
+/// finite and >= 0, durations finite and > 0.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse_trace(text: &str) -> Result<Vec<TraceTask>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(n, line)| {
+            let err = |e: String| format!("Trace line {}: {}", n + 1, e);
+            let mut items = line.split_whitespace();
+            let mut number = |name: &str| -> Result<f64, String> {
+                items
+                    .next()
+                    .ok_or_else(|| format!("missing {name}"))?
+                    .parse::<f64>()
+                    .map_err(|e| format!("invalid {name}: {e}"))
+            };
+            let arrival = number("arrival").map_err(err)?;
+            let duration = number("duration").map_err(err)?;
+            if !(arrival.is_finite() && arrival >= 0.0) {
+                return Err(err(format!(
+                    "arrival must be finite and >= 0, got {arrival}"
+                )));
+            }
+            if !(duration.is_finite() && duration > 0.0) {
+                return Err(err(format!(
+                    "duration must be finite and > 0, got {duration}"
+                )));
+            }
+            let requests = items
+                .map(|item| item.parse::<Amount>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(err)?;
+            Ok(TraceTask {
+                arrival,
+                duration,
+                requests,
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct Sample {
+    pub time: f64,
+    pub running: usize,
+    pub queued: usize,
+    /// Allocated share of the node capacity per group
+    pub utilization: Vec<f64>,
+    /// Share of free capacity that lies on partially used indices, per group
+    pub fragmentation: Vec<f64>,
+}
+
+pub struct SimulationReport {
+    pub samples: Vec<Sample>,
+    pub finished: usize,
+    /// Tasks that did not fit even on the empty node
+    pub rejected: usize,
+    /// Tasks that started at their arrival time
+    pub started_immediately: usize,
+    pub waits: Vec<f64>,
+    /// Time-weighted mean of utilization per group
+    pub mean_utilization: Vec<f64>,
+    /// Sum of connection and hyper connection weights achieved by each allocation
+    pub affinities: Vec<f64>,
+    pub end_time: f64,
+}
+
+struct Running {
+    end: f64,
+    solution: HqSolution,
+}
+
+fn take_sample(
+    time: f64,
+    capacity: &State,
+    state: &State,
+    running: usize,
+    queued: usize,
+) -> Sample {
+    let mut utilization = Vec::new();
+    let mut fragmentation = Vec::new();
+    for (total, current) in capacity.groups.iter().zip(&state.groups) {
+        let mut all = 0;
+        let mut free = 0;
+        let mut fragmented = 0;
+        for (t, c) in total.free.iter().zip(&current.free) {
+            let (t, c) = (t.as_fractions(), c.as_fractions());
+            all += t;
+            free += c;
+            if c > 0 && c < t {
+                fragmented += c;
+            }
+        }
+        let ratio = |a: u64, b: u64| if b == 0 { 0.0 } else { a as f64 / b as f64 };
+        utilization.push(ratio(all - free, all));
+        fragmentation.push(ratio(fragmented, free));
+    }
+    Sample {
+        time,
+        running,
+        queued,
+        utilization,
+        fragmentation,
+    }
+}
+
+/// Serves the trace on one node in first-come-first-served order: a task waits until it and
+/// all tasks queued before it have been allocated. `solve` is called with the current free
+/// amounts and the task requests set in the state. An error of `solve` ends the simulation.
+pub fn simulate(
+    mut state: State,
+    trace: &[TraceTask],
+    sample_interval: f64,
+    mut solve: impl FnMut(&State) -> Result<Option<HqSolution>, String>,
+) -> Result<SimulationReport, String> {
+    let capacity = state.clone();
+    let mut order: Vec<usize> = (0..trace.len()).collect();
+    order.sort_by(|a, b| trace[*a].arrival.total_cmp(&trace[*b].arrival));
+    let mut arrivals: VecDeque<usize> = order.into();
+
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    let mut running: Vec<Running> = Vec::new();
+    let mut report = SimulationReport {
+        samples: Vec::new(),
+        finished: 0,
+        rejected: 0,
+        started_immediately: 0,
+        waits: Vec::new(),
+        mean_utilization: vec![0.0; state.groups.len()],
+        affinities: Vec::new(),
+        end_time: 0.0,
+    };
+    let mut time = 0.0;
+    let mut next_sample = 0.0;
+    let mut last = take_sample(0.0, &capacity, &state, 0, 0);
+
+    loop {
+        let next_arrival = arrivals.front().map(|&t| trace[t].arrival);
+        let next_end = running.iter().map(|r| r.end).min_by(f64::total_cmp);
+        let next = match (next_arrival, next_end) {
+            (Some(a), Some(e)) => a.min(e),
+            (Some(a), None) => a,
+            (None, Some(e)) => e,
+            (None, None) => break,
+        };
+        while next_sample <= next {
+            let mut sample = last.clone();
+            sample.time = next_sample;
+            report.samples.push(sample);
+            next_sample += sample_interval;
+        }
+        for (mean, u) in report.mean_utilization.iter_mut().zip(&last.utilization) {
+            *mean += u * (next - time);
+        }
+        time = next;
+
+        running.retain(|r| {
+            if r.end <= time {
+                // Allocated on this state before
+                state.release(&r.solution).unwrap();
+                report.finished += 1;
+                false
+            } else {
+                true
+            }
+        });
+        while arrivals.front().is_some_and(|&t| trace[t].arrival <= time) {
+            queue.push_back(arrivals.pop_front().unwrap());
+        }
+
+        while let Some(&t) = queue.front() {
+            let task = &trace[t];
+            state.set_requests(&task.requests);
+            let solution = solve(&state)?.filter(|s| state.allocate(s).is_ok());
+            match solution {
+                Some(solution) => {
+                    queue.pop_front();
+                    let wait = time - task.arrival;
+                    if wait == 0.0 {
+                        report.started_immediately += 1;
+                    }
+                    report.waits.push(wait);
+                    report.affinities.push(affinity(&state, &solution));
+                    running.push(Running {
+                        end: time + task.duration,
+                        solution,
+                    });
+                }
+                None if running.is_empty() => {
+                    // Nothing will be released, so the task never fits
+                    queue.pop_front();
+                    report.rejected += 1;
+                }
+                None => break,
+            }
+        }
+        last = take_sample(time, &capacity, &state, running.len(), queue.len());
+    }
+
+    report.end_time = time;
+    if time > 0.0 {
+        for mean in report.mean_utilization.iter_mut() {
+            *mean /= time;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_trace_rejects_invalid_times() {
+        let trace = parse_trace("# arrival duration requests\n0 1 2\n\n1.5 2 1.5000\n").unwrap();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[1].requests[0].as_fractions(), 15000);
+        for line in [
+            "1 inf 2", "nan 1 2", "1 -1 2", "1 0 2", "-1 1 2", "inf 1 2", "1 nan 2",
+        ] {
+            let error = parse_trace(&format!("0 1 1\n{line}")).err();
+            assert!(
+                error
+                    .as_ref()
+                    .is_some_and(|e| e.starts_with("Trace line 2:")),
+                "{line}: {error:?}"
+            );
+        }
+    }
+}
@@ -1,14 +1,282 @@
-use crate::state::State;
-use good_lp::{Expression, Solution, Solver, SolverModel, constraint, variable, variables};
+use crate::state::{Amount, Group, State, Task, ValidationError};
+use good_lp::{
+    Constraint, Expression, ProblemVariables, Solution, SolutionStatus, Solver, SolverModel,
+    Variable, constraint, variable, variables,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
+/// Selected indices per group, in the order of `State::groups`
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HqSolution {
     pub groups: Vec<Vec<usize>>,
+    /// Amount taken from each selected index, parallel to `groups`
+    pub amounts: Vec<Vec<Amount>>,
     pub objective: f64,
 }
 
-pub fn solve(state: &State, solver: impl Solver) -> Option<HqSolution> {
+/// Model variants and objective settings; the default is the plain weighted model
+#[derive(Debug, Default, Clone)]
+pub struct SolveOptions {
+    pub symmetry_breaking: bool,
+    /// Link connection variables to indices by one constraint per index instead of one per
+    /// connection endpoint (only for non-negative weights)
+    pub aggregate_connections: bool,
+    /// Reward for fully using the free units of indices, so less small leftovers stay behind.
+    /// Each drained index earns `fragmentation / (n + 1)`, where n is the number of indices that
+    /// can be drained, so the total stays below `fragmentation` and values up to 1024 keep
+    /// minimizing the index count first.
+    pub fragmentation: Option<f64>,
+    /// When not empty, the terms are optimized in this order instead of the weighted sum
+    pub lexicographic: Vec<ObjectiveTerm>,
+    /// Break ties between optimal solutions deterministically, so all backends select the same
+    /// indices (costs one extra solve per index not in the first solution)
+    pub canonical: bool,
+}
+
+/// Finds indices that can be swapped without changing the model, i.e. indices in the same group
+/// with the same free amount and the same connections (other endpoints and weight).
+/// Returns classes with at least two members for each group; members are in ascending order.
+pub fn symmetry_classes(state: &State) -> Vec<Vec<Vec<usize>>> {
+    // For every connection of an index: its other endpoints and weight
+    type Signature = Vec<(Vec<(usize, usize)>, u64)>;
+    let mut signatures: Vec<Vec<Signature>> = state
+        .groups
+        .iter()
+        .map(|g| vec![Vec::new(); g.free.len()])
+        .collect();
+    for c in &state.connections {
+        signatures[c.r1][c.g1].push((vec![(c.r2, c.g2)], c.weight.to_bits()));
+        signatures[c.r2][c.g2].push((vec![(c.r1, c.g1)], c.weight.to_bits()));
+    }
+    for h in &state.hyper_connections {
+        for &(r, g) in &h.members {
+            let mut others: Vec<_> = h
+                .members
+                .iter()
+                .filter(|m| **m != (r, g))
+                .copied()
+                .collect();
+            others.sort_unstable();
+            signatures[r][g].push((others, h.weight.to_bits()));
+        }
+    }
+    state
+        .groups
+        .iter()
+        .zip(signatures)
+        .map(|(group, mut sigs)| {
+            let mut classes: HashMap<_, Vec<usize>> = HashMap::new();
+            for (i, (free, sig)) in group.free.iter().zip(sigs.iter_mut()).enumerate() {
+                sig.sort_unstable();
+                classes
+                    .entry((free.units, free.fractions, std::mem::take(sig)))
+                    .or_default()
+                    .push(i);
+            }
+            let mut classes: Vec<_> = classes.into_values().filter(|c| c.len() > 1).collect();
+            classes.sort_unstable();
+            classes
+        })
+        .collect()
+}
+
+fn add_connection_vars(
+    state: &State,
+    variables: &mut ProblemVariables,
+    obj: &mut Expression,
+) -> (Vec<Variable>, Vec<Variable>) {
+    let conn_vars = state
+        .connections
+        .iter()
+        .map(|c| {
+            let v = variables.add(variable().min(0).max(1));
+            if !c.is_forbidden() {
+                obj.add_mul(c.weight, v);
+            }
+            v
+        })
+        .collect();
+
+    let hyper_vars = state
+        .hyper_connections
+        .iter()
+        .map(|h| {
+            let v = variables.add(variable().min(0).max(1));
+            obj.add_mul(h.weight, v);
+            v
+        })
+        .collect();
+    (conn_vars, hyper_vars)
+}
+
+fn connection_constraints(
+    state: &State,
+    gvars: &[Vec<Variable>],
+    conn_vars: &[Variable],
+    hyper_vars: &[Variable],
+    aggregate: bool,
+) -> Vec<Constraint> {
+    let mut constraints = Vec::new();
+    let mut incident: Vec<Vec<(Expression, usize)>> = gvars
+        .iter()
+        .map(|vars| vec![(0.into(), 0); vars.len()])
+        .collect();
+    for (conn, conn_var) in state.connections.iter().zip(conn_vars.iter()) {
+        let v1 = gvars[conn.r1][conn.g1];
+        let v2 = gvars[conn.r2][conn.g2];
+        if conn.is_forbidden() {
+            constraints.push(constraint!(v1 + v2 <= 1));
+        } else if conn.weight < 0.0 {
+            // Maximization pushes a penalized variable down, so it has to be bounded from below
+            constraints.push(constraint!(*conn_var >= v1 + v2 - 1));
+        } else if aggregate {
+            for (r, g) in [(conn.r1, conn.g1), (conn.r2, conn.g2)] {
+                let (sum, count) = &mut incident[r][g];
+                *sum += *conn_var;
+                *count += 1;
+            }
+        } else {
+            constraints.push(constraint!(*conn_var <= v1));
+            constraints.push(constraint!(*conn_var <= v2));
+        }
+    }
+    for (sums, vars) in incident.into_iter().zip(gvars.iter()) {
+        for ((sum, count), v) in sums.into_iter().zip(vars.iter()) {
+            if count > 0 {
+                let bound = count as f64 * *v;
+                constraints.push(constraint!(sum <= bound));
+            }
+        }
+    }
+
+    for (hyper, hyper_var) in state.hyper_connections.iter().zip(hyper_vars.iter()) {
+        if hyper.weight < 0.0 {
+            let mut all: Expression = (1.0 - hyper.members.len() as f64).into();
+            for &(r, g) in &hyper.members {
+                all += gvars[r][g];
+            }
+            constraints.push(constraint!(*hyper_var >= all));
+        } else {
+            for &(r, g) in &hyper.members {
+                let v = gvars[r][g];
+                constraints.push(constraint!(*hyper_var <= v));
+            }
+        }
+    }
+    constraints
+}
+
+/// Splits the group request over the selected indices, taking at most `units[k]` units from
+/// `selected[k]`. The fractional part goes to an index with enough free fractions (or is cut
+/// from a unit that is not taken whole), units are taken in index order.
+fn distribute(group: &Group, selected: &[usize], mut units: Vec<u32>) -> Vec<Amount> {
+    let request = group.request;
+    let mut amounts = vec![Amount::new_u(0); selected.len()];
+    if request.fractions > 0 {
+        let pos = selected
+            .iter()
+            .position(|&i| group.free[i].fractions >= request.fractions)
+            .or_else(|| (0..units.len()).rfind(|&k| group.free[selected[k]].units > units[k]))
+            .or_else(|| units.iter().rposition(|&u| u > 0));
+        if let Some(pos) = pos {
+            let free = group.free[selected[pos]];
+            if free.fractions < request.fractions && units[pos] == free.units {
+                units[pos] -= 1;
+            }
+            amounts[pos].fractions = request.fractions;
+        }
+    }
+    let mut remaining = request.units;
+    for (amount, units) in amounts.iter_mut().zip(units) {
+        amount.units = units.min(remaining);
+        remaining -= amount.units;
+    }
+    amounts
+}
+
+/// Components of the objective, each of them maximized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectiveTerm {
+    /// Negated number of selected indices
+    IndexCount,
+    /// Sum of achieved connection and hyper connection weights
+    Affinity,
+    /// Tie-break preferring indices with less free resources, so bigger ones stay available
+    Leftovers,
+    /// Number of indices drained by the allocation
+    Fragmentation,
+}
+
+impl FromStr for ObjectiveTerm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "index-count" => Ok(ObjectiveTerm::IndexCount),
+            "affinity" => Ok(ObjectiveTerm::Affinity),
+            "leftovers" => Ok(ObjectiveTerm::Leftovers),
+            "fragmentation" => Ok(ObjectiveTerm::Fragmentation),
+            _ => Err(format!("Invalid objective term: {s}")),
+        }
+    }
+}
+
+struct Terms {
+    index_count: Expression,
+    affinity: Expression,
+    leftovers: Expression,
+    fragmentation: Expression,
+    /// Number of drain variables in `fragmentation`
+    drainable: usize,
+}
+
+impl Terms {
+    fn goal(&self, goal: Goal, options: &SolveOptions) -> Expression {
+        match goal {
+            Goal::Weighted => self.weighted(options),
+            Goal::Term(ObjectiveTerm::IndexCount) => self.index_count.clone(),
+            Goal::Term(ObjectiveTerm::Affinity) => self.affinity.clone(),
+            Goal::Term(ObjectiveTerm::Leftovers) => self.leftovers.clone(),
+            Goal::Term(ObjectiveTerm::Fragmentation) => self.fragmentation.clone(),
+        }
+    }
+
+    fn weighted(&self, options: &SolveOptions) -> Expression {
+        let mut obj = 1024.0 * self.index_count.clone();
+        obj += self.affinity.clone();
+        obj += self.leftovers.clone();
+        obj.add_mul(
+            options.fragmentation.unwrap_or(0.0) / (self.drainable + 1) as f64,
+            self.fragmentation.clone(),
+        );
+        obj
+    }
+}
+
+// Units taken from each index and whether the index is drained by it
+type DrainVars = Vec<Vec<(Variable, Option<Variable>)>>;
+
+struct ModelVars {
+    gvars: Vec<Vec<Variable>>,
+    drain_vars: Option<DrainVars>,
+    terms: Terms,
+}
+
+fn build_model(
+    state: &State,
+    options: &SolveOptions,
+) -> (ProblemVariables, ModelVars, Vec<Constraint>) {
     let mut variables = variables!();
-    let mut obj: Expression = 0.into();
+    let mut terms = Terms {
+        index_count: 0.into(),
+        affinity: 0.into(),
+        leftovers: 0.into(),
+        fragmentation: 0.into(),
+        drainable: 0,
+    };
 
     let gvars: Vec<Vec<_>> = state
         .groups
@@ -19,14 +287,13 @@ pub fn solve(state: &State, solver: impl Solver) -> Option<HqSolution> {
                 .iter()
                 .map(|free| {
                     let v = variables.add(variable().binary());
+                    terms.index_count -= v;
                     if rf == 0 {
-                        obj.add_mul(-1024.0 - (free.units as f64 / 32.0), v);
+                        terms.leftovers.add_mul(-(free.units as f64 / 32.0), v);
                     } else {
                         let f = free.fractions;
                         if f >= rf {
-                            obj.add_mul(-1024.0 - (f as f64 / 10_000.0 / 16.0), v);
-                        } else {
-                            obj.add_mul(-1024.0, v);
+                            terms.leftovers.add_mul(-(f as f64 / 10_000.0 / 16.0), v);
                         }
                     }
                     v
@@ -35,25 +302,42 @@ pub fn solve(state: &State, solver: impl Solver) -> Option<HqSolution> {
         })
         .collect();
 
-    let conn_vars: Vec<_> = state
-        .connections
-        .iter()
-        .map(|c| {
-            let v = variables.add(variable());
-            obj.add_mul(c.weight, v);
-            v
-        })
-        .collect();
+    let (conn_vars, hyper_vars) = add_connection_vars(state, &mut variables, &mut terms.affinity);
 
-    let mut p = variables.maximise(&obj).using(solver);
+    let drain_vars: Option<DrainVars> = (options.fragmentation.is_some()
+        || options
+            .lexicographic
+            .contains(&ObjectiveTerm::Fragmentation))
+    .then(|| {
+        state
+            .groups
+            .iter()
+            .map(|g| {
+                g.free
+                    .iter()
+                    .map(|free| {
+                        let taken = variables.add(variable().integer().min(0).max(free.units));
+                        let drained = (free.units > 0 && free.fractions == 0).then(|| {
+                            let v = variables.add(variable().binary());
+                            terms.fragmentation += v;
+                            terms.drainable += 1;
+                            v
+                        });
+                        (taken, drained)
+                    })
+                    .collect()
+            })
+            .collect()
+    });
 
+    let mut constraints: Vec<Constraint> = Vec::new();
     for (group, vars) in state.groups.iter().zip(gvars.iter()) {
         if group.request.units > 0 {
             let mut cst: Expression = 0.into();
             for (c, v) in group.free.iter().zip(vars.iter()) {
                 cst.add_mul(c.units as f64, *v);
             }
-            p.add_constraint(constraint!(cst >= group.request.units as f64));
+            constraints.push(constraint!(cst >= group.request.units as f64));
         }
         let rf = group.request.fractions;
         if rf > 0 {
@@ -66,32 +350,1077 @@ pub fn solve(state: &State, solver: impl Solver) -> Option<HqSolution> {
                 };
                 cst.add_mul(units as f64, *v);
             }
-            p.add_constraint(constraint!(cst >= (group.request.units + 1) as f64));
+            constraints.push(constraint!(cst >= (group.request.units + 1) as f64));
         }
     }
 
-    for (conn, conn_var) in state.connections.iter().zip(conn_vars.iter()) {
-        let v1 = gvars[conn.r1][conn.g1];
-        let v2 = gvars[conn.r2][conn.g2];
-        p.add_constraint(constraint!(*conn_var <= v1));
-        p.add_constraint(constraint!(*conn_var <= v2));
+    constraints.extend(connection_constraints(
+        state,
+        &gvars,
+        &conn_vars,
+        &hyper_vars,
+        options.aggregate_connections,
+    ));
+
+    if let Some(drain_vars) = &drain_vars {
+        for ((group, vars), drains) in state.groups.iter().zip(&gvars).zip(drain_vars) {
+            let mut sum: Expression = 0.into();
+            for ((free, v), (taken, drained)) in group.free.iter().zip(vars).zip(drains) {
+                let units = free.units as f64;
+                constraints.push(constraint!(*taken <= units * *v));
+                if let Some(drained) = drained {
+                    constraints.push(constraint!(units * *drained <= *taken));
+                }
+                sum += *taken;
+            }
+            constraints.push(constraint!(sum == group.request.units as f64));
+        }
+    }
+
+    if options.symmetry_breaking {
+        for (classes, vars) in symmetry_classes(state).iter().zip(gvars.iter()) {
+            for class in classes {
+                for w in class.windows(2) {
+                    let (v1, v2) = (vars[w[0]], vars[w[1]]);
+                    constraints.push(constraint!(v1 >= v2));
+                }
+            }
+        }
     }
 
-    let solution = p.solve().ok()?;
-    let objective = solution.eval(obj);
+    let vars = ModelVars {
+        gvars,
+        drain_vars,
+        terms,
+    };
+    (variables, vars, constraints)
+}
 
-    let result: Vec<_> = gvars
+fn extract_solution(
+    state: &State,
+    vars: &ModelVars,
+    solution: &impl Solution,
+    options: &SolveOptions,
+) -> HqSolution {
+    let result: Vec<_> = vars
+        .gvars
         .iter()
         .map(|vars| {
             vars.iter()
                 .enumerate()
-                .filter_map(|(i, v)| (solution.value(*v) > 0.5).then(|| i))
+                .filter(|(_, v)| solution.value(**v) > 0.5)
+                .map(|(i, _)| i)
                 .collect::<Vec<usize>>()
         })
         .collect();
+    let amounts = state
+        .groups
+        .iter()
+        .zip(result.iter())
+        .enumerate()
+        .map(|(r, (group, selected))| {
+            let units = selected
+                .iter()
+                .map(|&i| match &vars.drain_vars {
+                    Some(drain_vars) => solution.value(drain_vars[r][i].0).round() as u32,
+                    None => group.free[i].units,
+                })
+                .collect();
+            distribute(group, selected, units)
+        })
+        .collect();
 
-    Some(HqSolution {
+    HqSolution {
         groups: result,
-        objective,
-    })
+        amounts,
+        objective: solution.eval(vars.terms.weighted(options)),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Goal {
+    Weighted,
+    Term(ObjectiveTerm),
+}
+
+fn goals(options: &SolveOptions) -> Vec<Goal> {
+    if options.lexicographic.is_empty() {
+        vec![Goal::Weighted]
+    } else {
+        options
+            .lexicographic
+            .iter()
+            .map(|t| Goal::Term(*t))
+            .collect()
+    }
+}
+
+/// Bound used to keep the optimum `value` of `goal` in later models. The slack is below half the
+/// step between two values of the goal: counts are integers and leftovers are multiples of
+/// 1/160000, affinity only gets a margin for rounding errors in the sum of its weights.
+fn lower_bound(state: &State, goal: Goal, value: f64) -> f64 {
+    const LEFTOVERS_STEP: f64 = 1.0 / 10_000.0 / 16.0;
+    let affinity = affinity_margin(state);
+    let slack = match goal {
+        Goal::Term(ObjectiveTerm::IndexCount | ObjectiveTerm::Fragmentation) => 0.5,
+        Goal::Term(ObjectiveTerm::Leftovers) => LEFTOVERS_STEP / 2.0,
+        Goal::Term(ObjectiveTerm::Affinity) => affinity,
+        Goal::Weighted => affinity.min(LEFTOVERS_STEP / 2.0),
+    };
+    value - slack
+}
+
+/// Rounding error allowed on the affinity of a selection, relative to the sum of all weights
+fn affinity_margin(state: &State) -> f64 {
+    let weights = state
+        .connections
+        .iter()
+        .filter(|c| !c.is_forbidden())
+        .map(|c| c.weight.abs())
+        .chain(state.hyper_connections.iter().map(|h| h.weight.abs()))
+        .sum::<f64>();
+    1e-9 * weights.max(1.0)
+}
+
+/// A solution with every variable rounded, so goals are evaluated on the integral selection
+/// without the noise of the solver tolerances
+struct Rounded<'a, S>(&'a S);
+
+impl<S: Solution> Solution for Rounded<'_, S> {
+    fn status(&self) -> SolutionStatus {
+        self.0.status()
+    }
+
+    fn value(&self, variable: Variable) -> f64 {
+        self.0.value(variable).round()
+    }
+}
+
+/// Constraints added to the model on top of the state
+#[derive(Default)]
+struct Restrictions {
+    /// Indices forced to be selected (true) or not (false)
+    fixed: Vec<((usize, usize), bool)>,
+    /// Selections that must not be found again
+    excluded: Vec<Vec<Vec<usize>>>,
+    minimums: Vec<(Goal, f64)>,
+}
+
+impl Restrictions {
+    fn constraints(&self, vars: &ModelVars, options: &SolveOptions) -> Vec<Constraint> {
+        let mut constraints = Vec::new();
+        for &((r, g), selected) in &self.fixed {
+            let v = vars.gvars[r][g];
+            constraints.push(constraint!(v == if selected { 1 } else { 0 }));
+        }
+        for groups in &self.excluded {
+            // At least one index must change: sum of unselected - sum of selected >= 1 - |selected|
+            let mut cut: Expression = 0.into();
+            let mut n_selected = 0;
+            for (vars, selected) in vars.gvars.iter().zip(groups) {
+                for (i, v) in vars.iter().enumerate() {
+                    if selected.contains(&i) {
+                        cut -= *v;
+                        n_selected += 1;
+                    } else {
+                        cut += *v;
+                    }
+                }
+            }
+            constraints.push(constraint!(cut >= 1 - n_selected));
+        }
+        for (goal, value) in &self.minimums {
+            let expr = vars.terms.goal(*goal, options);
+            constraints.push(constraint!(expr >= *value));
+        }
+        constraints
+    }
+}
+
+/// Optimizes `goals` one after another; each stage keeps the optima of all previous goals
+/// as constraints. Returns the solution with the optimum of every goal. The reported objective
+/// is the weighted one. The state must be valid.
+fn solve_staged(
+    state: &State,
+    solver: impl Solver + Copy,
+    options: &SolveOptions,
+    goals: &[Goal],
+    restrictions: &Restrictions,
+) -> Option<(HqSolution, Vec<f64>)> {
+    let mut minimums = Vec::new();
+    let mut optima = Vec::new();
+    let mut result = None;
+    for &goal in goals {
+        let (variables, vars, mut constraints) = build_model(state, options);
+        constraints.extend(restrictions.constraints(&vars, options));
+        for (previous, value) in &minimums {
+            let expr = vars.terms.goal(*previous, options);
+            constraints.push(constraint!(expr >= *value));
+        }
+        let obj = vars.terms.goal(goal, options);
+        let solution = variables
+            .maximise(&obj)
+            .using(solver)
+            .with_all(constraints)
+            .solve()
+            .ok()?;
+        let value = Rounded(&solution).eval(&obj);
+        minimums.push((goal, lower_bound(state, goal, value)));
+        optima.push(value);
+        result = Some(extract_solution(state, &vars, &solution, options));
+    }
+    result.map(|solution| (solution, optima))
+}
+
+/// Among the optimal selections, picks the one that selects the earliest indices: indices are
+/// visited in order and each is kept selected if some optimal solution still allows it.
+fn canonicalize(
+    state: &State,
+    solver: impl Solver + Copy,
+    options: &SolveOptions,
+    goals: &[Goal],
+    optima: &[f64],
+    mut solution: HqSolution,
+) -> HqSolution {
+    let mut restrictions = Restrictions {
+        minimums: goals
+            .iter()
+            .zip(optima)
+            .map(|(goal, value)| (*goal, lower_bound(state, *goal, *value)))
+            .collect(),
+        ..Default::default()
+    };
+    let last = &goals[goals.len() - 1..];
+    for (r, group) in state.groups.iter().enumerate() {
+        for g in 0..group.free.len() {
+            restrictions.fixed.push(((r, g), true));
+            if !solution.groups[r].contains(&g) {
+                match solve_staged(state, solver, options, last, &restrictions) {
+                    Some((s, _)) => solution = s,
+                    None => restrictions.fixed.last_mut().unwrap().1 = false,
+                }
+            }
+        }
+    }
+    solution
+}
+
+/// Selects indices for the requests of `state`. Returns None when the requests cannot be
+/// satisfied (see `diagnose`), or the problems of an invalid state.
+pub fn solve(
+    state: &State,
+    solver: impl Solver + Copy,
+    options: &SolveOptions,
+) -> Result<Option<HqSolution>, Vec<ValidationError>> {
+    state.validate()?;
+    let goals = goals(options);
+    let Some((solution, optima)) =
+        solve_staged(state, solver, options, &goals, &Restrictions::default())
+    else {
+        return Ok(None);
+    };
+    if options.canonical {
+        Ok(Some(canonicalize(
+            state, solver, options, &goals, &optima, solution,
+        )))
+    } else {
+        Ok(Some(solution))
+    }
+}
+
+/// Enumerates up to `k` distinct selections in order of decreasing objective, cutting off every
+/// found selection with a no-good constraint. With `optimal_only`, stops at the first selection
+/// worse than the best one. Symmetry breaking hides selections that only differ by swapping
+/// interchangeable indices.
+pub fn solve_top_k(
+    state: &State,
+    solver: impl Solver + Copy,
+    options: &SolveOptions,
+    k: usize,
+    optimal_only: bool,
+) -> Result<Vec<HqSolution>, Vec<ValidationError>> {
+    state.validate()?;
+    let goals = goals(options);
+    let mut restrictions = Restrictions::default();
+    let mut best: Option<Vec<f64>> = None;
+    let mut solutions = Vec::new();
+    while solutions.len() < k {
+        let Some((solution, optima)) = solve_staged(state, solver, options, &goals, &restrictions)
+        else {
+            break;
+        };
+        let best = best.get_or_insert_with(|| optima.clone());
+        let worse = goals
+            .iter()
+            .zip(best.iter().zip(&optima))
+            .any(|(goal, (b, o))| *o < lower_bound(state, *goal, *b));
+        if optimal_only && worse {
+            break;
+        }
+        restrictions.excluded.push(solution.groups.clone());
+        solutions.push(solution);
+    }
+    Ok(solutions)
+}
+
+/// Sum of connection and hyper connection weights achieved by a solution
+pub fn affinity(state: &State, solution: &HqSolution) -> f64 {
+    let selected = |r: usize, g: usize| solution.groups[r].contains(&g);
+    let pairs: f64 = state
+        .connections
+        .iter()
+        .filter(|c| !c.is_forbidden() && selected(c.r1, c.g1) && selected(c.r2, c.g2))
+        .fold(0.0, |sum, c| sum + c.weight);
+    let hyper: f64 = state
+        .hyper_connections
+        .iter()
+        .filter(|h| h.members.iter().all(|&(r, g)| selected(r, g)))
+        .fold(0.0, |sum, h| sum + h.weight);
+    pairs + hyper
+}
+
+/// Pareto-optimal solutions trading the number of selected indices against affinity, starting
+/// from the fewest indices. Each next point is found by requiring more affinity than the
+/// previous one by the affinity rounding margin (epsilon-constraint), then minimizing the index
+/// count, maximizing affinity and breaking ties by leftovers. Found selections are excluded, so
+/// solver tolerances cannot return the same point again.
+pub fn pareto_front(
+    state: &State,
+    solver: impl Solver + Copy,
+    options: &SolveOptions,
+) -> Result<Vec<HqSolution>, Vec<ValidationError>> {
+    state.validate()?;
+    let order = [
+        Goal::Term(ObjectiveTerm::IndexCount),
+        Goal::Term(ObjectiveTerm::Affinity),
+        Goal::Term(ObjectiveTerm::Leftovers),
+    ];
+    let mut front = Vec::new();
+    let mut restrictions = Restrictions::default();
+    let margin = affinity_margin(state);
+    let mut previous = f64::NEG_INFINITY;
+    while let Some((solution, _)) = solve_staged(state, solver, options, &order, &restrictions) {
+        let value = affinity(state, &solution);
+        restrictions.minimums = vec![(Goal::Term(ObjectiveTerm::Affinity), value + margin)];
+        restrictions.excluded.push(solution.groups.clone());
+        // Equal affinity within the solver tolerance is dominated by the previous point
+        if value > previous {
+            previous = value;
+            front.push(solution);
+        }
+    }
+    Ok(front)
+}
+
+/// Reason why the requests of a state cannot be satisfied
+#[derive(Debug, Clone)]
+pub enum Infeasibility {
+    /// The group has fewer free units than requested
+    Capacity {
+        group: usize,
+        requested: u32,
+        free: u32,
+    },
+    /// The fractional part of the request fits on no index and all free units are needed for
+    /// the whole units, so none can be split
+    Fractions {
+        group: usize,
+        requested: Amount,
+        largest_fraction: u32,
+    },
+    /// Every group fits on its own, but forbidden connections rule out all combinations
+    Conflict { forbidden: usize },
+    /// The state itself is malformed
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for Infeasibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Infeasibility::Capacity {
+                group,
+                requested,
+                free,
+            } => write!(
+                f,
+                "group {group}: requested {requested} units, only {free} free (short by {})",
+                requested - free
+            ),
+            Infeasibility::Fractions {
+                group,
+                requested,
+                largest_fraction,
+            } => write!(
+                f,
+                "group {group}: requested {requested}, no index has {} free fractions (largest {}) \
+                 and no spare unit to split",
+                requested.fractions, largest_fraction
+            ),
+            Infeasibility::Conflict { forbidden } => write!(
+                f,
+                "every group fits alone, but the forbidden connections ({forbidden}) exclude \
+                 all combinations"
+            ),
+            Infeasibility::Invalid(error) => write!(f, "invalid state: {error}"),
+        }
+    }
+}
+
+/// Explains why `solve` found no solution for `state`. Capacity and fractional shortfalls are
+/// checked per group without a solver; when all groups fit on their own, forbidden connections
+/// are the only remaining cause, so `Conflict` is only meaningful after a failed solve.
+pub fn diagnose(state: &State) -> Vec<Infeasibility> {
+    if let Err(errors) = state.validate() {
+        return errors.into_iter().map(Infeasibility::Invalid).collect();
+    }
+    let mut reasons = Vec::new();
+    for (r, group) in state.groups.iter().enumerate() {
+        let request = group.request;
+        let free: u32 = group.free.iter().map(|a| a.units).sum();
+        if free < request.units {
+            reasons.push(Infeasibility::Capacity {
+                group: r,
+                requested: request.units,
+                free,
+            });
+            continue;
+        }
+        if request.fractions > 0 && free == request.units {
+            let largest_fraction = group.free.iter().map(|a| a.fractions).max().unwrap_or(0);
+            if largest_fraction < request.fractions {
+                reasons.push(Infeasibility::Fractions {
+                    group: r,
+                    requested: request,
+                    largest_fraction,
+                });
+            }
+        }
+    }
+    let forbidden = state
+        .connections
+        .iter()
+        .filter(|c| c.is_forbidden())
+        .count();
+    if reasons.is_empty() && forbidden > 0 {
+        reasons.push(Infeasibility::Conflict { forbidden });
+    }
+    reasons
+}
+
+/// Checks that a solution is a valid allocation of the requests in `state`: every selected index
+/// exists and has enough free resources, each group gets exactly its requested units with the
+/// fractional part taken from a single index, and no forbidden pair is selected.
+pub fn check_solution(state: &State, solution: &HqSolution) -> Result<(), String> {
+    if solution.groups.len() != state.groups.len() || solution.amounts.len() != state.groups.len() {
+        return Err(format!(
+            "Solution has {} groups, state has {}",
+            solution.groups.len(),
+            state.groups.len()
+        ));
+    }
+    for (r, ((group, indices), amounts)) in state
+        .groups
+        .iter()
+        .zip(&solution.groups)
+        .zip(&solution.amounts)
+        .enumerate()
+    {
+        if indices.len() != amounts.len() {
+            return Err(format!("Group {r}: indices and amounts differ in length"));
+        }
+        if indices.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(format!("Group {r}: indices are not sorted and unique"));
+        }
+        for (&i, amount) in indices.iter().zip(amounts) {
+            let free = group
+                .free
+                .get(i)
+                .ok_or_else(|| format!("Group {r}: index {i} does not exist"))?;
+            if free.checked_sub(amount).is_none() {
+                return Err(format!(
+                    "Group {r}: index {i} has {free} free, {amount} allocated"
+                ));
+            }
+        }
+        let units: u32 = amounts.iter().map(|a| a.units).sum();
+        let fractions: Vec<u32> = amounts
+            .iter()
+            .map(|a| a.fractions)
+            .filter(|f| *f > 0)
+            .collect();
+        let expected: &[u32] = if group.request.fractions > 0 {
+            &[group.request.fractions]
+        } else {
+            &[]
+        };
+        if units != group.request.units || fractions != expected {
+            let allocated = amounts
+                .iter()
+                .fold(Amount::default(), |sum, amount| sum + *amount);
+            return Err(format!(
+                "Group {r}: requested {}, allocated {allocated}",
+                group.request
+            ));
+        }
+    }
+    let selected = |r: usize, g: usize| solution.groups[r].binary_search(&g).is_ok();
+    for c in state.connections.iter().filter(|c| c.is_forbidden()) {
+        if selected(c.r1, c.g1) && selected(c.r2, c.g2) {
+            return Err(format!(
+                "Forbidden pair ({}, {}) and ({}, {}) is selected",
+                c.r1, c.g1, c.r2, c.g2
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Solves the given requests against the current free amounts of `state` and, when a solution
+/// is found, allocates it, so the state is ready for the next request. Fails with the
+/// validation errors of the state, or when the solution cannot be allocated.
+pub fn solve_and_allocate(
+    state: &mut State,
+    requests: &[Amount],
+    solver: impl Solver + Copy,
+    options: &SolveOptions,
+) -> Result<Option<HqSolution>, String> {
+    state.set_requests(requests);
+    let solution = solve(state, solver, options).map_err(|errors| {
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        errors.join("; ")
+    })?;
+    let Some(solution) = solution else {
+        return Ok(None);
+    };
+    state
+        .allocate(&solution)
+        .map_err(|e| format!("Cannot allocate the solution: {e}"))?;
+    Ok(Some(solution))
+}
+
+struct TaskVars {
+    accept: Variable,
+    select: Vec<Vec<Variable>>,
+    units: Vec<Vec<Variable>>,
+    fractions: Vec<Vec<Option<Variable>>>,
+    obj: Expression,
+}
+
+/// Allocates several tasks at once, `tasks[t].requests[r]` takes the place of
+/// `state.groups[r].request`. Free amounts of an index are shared by all tasks without overcommit;
+/// a whole unit is never split between tasks, free fractions and fractions cut from a unit
+/// are treated as one divisible pool. Returns a solution for each accepted task and `None` for
+/// tasks that do not fit. The accepted tasks maximize the sum of priorities: the reward for
+/// accepting the task with the lowest priority outweighs the index and connection terms of all
+/// tasks together. Symmetry breaking, fragmentation, lexicographic and canonical solving are
+/// not supported.
+pub fn solve_tasks(
+    state: &State,
+    tasks: &[Task],
+    solver: impl Solver,
+    options: &SolveOptions,
+) -> Result<Option<Vec<Option<HqSolution>>>, Vec<ValidationError>> {
+    let mut errors = state.validate_tasks(tasks).err().unwrap_or_default();
+    let unsupported = [
+        ("symmetry_breaking", options.symmetry_breaking),
+        ("fragmentation", options.fragmentation.is_some()),
+        ("lexicographic", !options.lexicographic.is_empty()),
+        ("canonical", options.canonical),
+    ];
+    for (option, _) in unsupported.into_iter().filter(|(_, set)| *set) {
+        errors.push(ValidationError::UnsupportedOption { option });
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut variables = variables!();
+    let mut obj: Expression = 0.into();
+    let mut constraints = Vec::new();
+
+    let index_costs: f64 = state
+        .groups
+        .iter()
+        .flat_map(|g| &g.free)
+        .map(|free| 1024.0 + free.units as f64 / 32.0)
+        .sum();
+    let total_weight: f64 = state
+        .connections
+        .iter()
+        .filter(|c| !c.is_forbidden())
+        .map(|c| c.weight.abs())
+        .chain(state.hyper_connections.iter().map(|h| h.weight.abs()))
+        .sum();
+    // More than the index and connection terms of all tasks can differ by
+    let accept_bonus = tasks.len() as f64 * (index_costs + 2.0 * total_weight) + 1.0;
+    let min_priority = tasks
+        .iter()
+        .map(|t| t.priority)
+        .fold(f64::INFINITY, f64::min);
+
+    let task_vars: Vec<TaskVars> = tasks
+        .iter()
+        .map(|task| {
+            let accept = variables.add(variable().binary());
+            obj.add_mul(task.priority / min_priority * accept_bonus, accept);
+            let mut task_obj: Expression = 0.into();
+            let mut select = Vec::new();
+            let mut units = Vec::new();
+            let mut fractions = Vec::new();
+            for (group, request) in state.groups.iter().zip(task.requests.iter()) {
+                let mut s = Vec::new();
+                let mut u = Vec::new();
+                let mut f = Vec::new();
+                let mut u_sum: Expression = 0.into();
+                let mut f_sum: Expression = 0.into();
+                for free in &group.free {
+                    let sv = variables.add(variable().binary());
+                    task_obj.add_mul(-1024.0 - (free.units as f64 / 32.0), sv);
+                    let uv = variables.add(variable().integer().min(0).max(free.units));
+                    constraints.push(constraint!(uv <= free.units as f64 * sv));
+                    u_sum += uv;
+                    let fv = (request.fractions > 0).then(|| {
+                        let fv = variables.add(variable().binary());
+                        constraints.push(constraint!(fv <= sv));
+                        f_sum += fv;
+                        fv
+                    });
+                    s.push(sv);
+                    u.push(uv);
+                    f.push(fv);
+                }
+                constraints.push(constraint!(u_sum == request.units as f64 * accept));
+                if request.fractions > 0 {
+                    constraints.push(constraint!(f_sum == accept));
+                }
+                select.push(s);
+                units.push(u);
+                fractions.push(f);
+            }
+            let (conn_vars, hyper_vars) = add_connection_vars(state, &mut variables, &mut task_obj);
+            constraints.extend(connection_constraints(
+                state,
+                &select,
+                &conn_vars,
+                &hyper_vars,
+                options.aggregate_connections,
+            ));
+            obj += task_obj.clone();
+            TaskVars {
+                accept,
+                select,
+                units,
+                fractions,
+                obj: task_obj,
+            }
+        })
+        .collect();
+
+    for (r, group) in state.groups.iter().enumerate() {
+        for (i, free) in group.free.iter().enumerate() {
+            let mut units: Expression = 0.into();
+            let mut capacity: Expression = 0.into();
+            for (task, vars) in tasks.iter().zip(task_vars.iter()) {
+                units += vars.units[r][i];
+                capacity.add_mul(10_000.0, vars.units[r][i]);
+                if let Some(f) = vars.fractions[r][i] {
+                    capacity.add_mul(task.requests[r].fractions as f64, f);
+                }
+            }
+            let total = free.units as f64 * 10_000.0 + free.fractions as f64;
+            constraints.push(constraint!(units <= free.units));
+            constraints.push(constraint!(capacity <= total));
+        }
+    }
+
+    let Ok(solution) = variables
+        .maximise(&obj)
+        .using(solver)
+        .with_all(constraints)
+        .solve()
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        tasks
+            .iter()
+            .zip(task_vars)
+            .map(|(task, vars)| {
+                if solution.value(vars.accept) < 0.5 {
+                    return None;
+                }
+                let mut groups = Vec::new();
+                let mut amounts = Vec::new();
+                for (((s, u), f), request) in vars
+                    .select
+                    .iter()
+                    .zip(&vars.units)
+                    .zip(&vars.fractions)
+                    .zip(&task.requests)
+                {
+                    let selected: Vec<usize> = (0..s.len())
+                        .filter(|&i| solution.value(s[i]) > 0.5)
+                        .collect();
+                    amounts.push(
+                        selected
+                            .iter()
+                            .map(|&i| {
+                                let units = solution.value(u[i]).round() as u32;
+                                let fractions = f[i]
+                                    .filter(|fv| solution.value(*fv) > 0.5)
+                                    .map_or(0, |_| request.fractions);
+                                Amount::new(units, fractions)
+                            })
+                            .collect(),
+                    );
+                    groups.push(selected);
+                }
+                Some(HqSolution {
+                    groups,
+                    amounts,
+                    objective: solution.eval(&vars.obj),
+                })
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::benchmarks;
+    use crate::builder::StateBuilder;
+    use good_lp::solvers::microlp::microlp;
+
+    fn canonical() -> SolveOptions {
+        SolveOptions {
+            canonical: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn canonical_keeps_leftovers_optimum() {
+        // {0, 1, 3} leaves 400 fractions less than {0, 1, 2}, which is visited first
+        let state = StateBuilder::new()
+            .group(|g| {
+                g.free([
+                    Amount::new(8, 0),
+                    Amount::new(2, 5000),
+                    Amount::new(2, 5400),
+                    Amount::new(2, 5000),
+                ])
+                .request_amount(Amount::new(12, 3500))
+            })
+            .build()
+            .unwrap();
+        let solution = solve(&state, microlp, &canonical()).unwrap().unwrap();
+        assert_eq!(solution.groups, vec![vec![0, 1, 3]]);
+    }
+
+    #[test]
+    fn canonical_fractions_16_is_optimal() {
+        let state = benchmarks::create_fractions_16();
+        let plain = solve(&state, microlp, &SolveOptions::default())
+            .unwrap()
+            .unwrap();
+        let canonical = solve(&state, microlp, &canonical()).unwrap().unwrap();
+        assert_eq!(canonical.objective, plain.objective);
+    }
+
+    /// Indices 0 and 1 of group 0 are swappable, 2 has another weight and 3 no connection
+    fn symmetric_state() -> State {
+        StateBuilder::new()
+            .group(|g| g.free_units([1; 4]).request(2))
+            .group(|g| g.free_units([1]).request(1))
+            .connect((0, 0), (1, 0), 5.0)
+            .connect((0, 1), (1, 0), 5.0)
+            .connect((0, 2), (1, 0), 6.0)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn symmetry_classes_follow_connections() {
+        // Even indices of groups 0 and 2 each connect to their own index of group 1
+        let state = benchmarks::create("diamonds_16_32_16").unwrap();
+        let odd: Vec<usize> = (1..32).step_by(2).collect();
+        let unconnected: Vec<usize> = (16..32).collect();
+        assert_eq!(
+            symmetry_classes(&state),
+            vec![vec![odd.clone()], vec![unconnected], vec![odd]]
+        );
+        assert_eq!(
+            symmetry_classes(&symmetric_state()),
+            vec![vec![vec![0, 1]], vec![]]
+        );
+    }
+
+    #[test]
+    fn symmetry_breaking_keeps_objective() {
+        // States with symmetry classes that microlp solves quickly
+        let symmetry = SolveOptions {
+            symmetry_breaking: true,
+            ..Default::default()
+        };
+        let states = ["empty_4", "fractions_16"]
+            .map(|name| (name, benchmarks::create(name).unwrap()))
+            .into_iter()
+            .chain([("symmetric", symmetric_state())]);
+        for (name, state) in states {
+            assert!(
+                symmetry_classes(&state).iter().any(|c| !c.is_empty()),
+                "{name}"
+            );
+            let plain = solve(&state, microlp, &SolveOptions::default())
+                .unwrap()
+                .unwrap();
+            let broken = solve(&state, microlp, &symmetry).unwrap().unwrap();
+            assert!(
+                (plain.objective - broken.objective).abs() < 1e-6,
+                "{name}: {} != {}",
+                plain.objective,
+                broken.objective
+            );
+        }
+    }
+
+    #[test]
+    fn aggregate_connections_keeps_objective() {
+        // Benchmarks with only non-negative weights that microlp solves quickly
+        let aggregate = SolveOptions {
+            aggregate_connections: true,
+            ..Default::default()
+        };
+        for name in [
+            "one2one_12_12",
+            "one2one_8_8_8",
+            "tree_4_4",
+            "tree_2_4_4",
+            "tree_2_2_4_2",
+        ] {
+            let state = benchmarks::create(name).unwrap();
+            assert!(state.connections.iter().all(|c| c.weight >= 0.0), "{name}");
+            let plain = solve(&state, microlp, &SolveOptions::default())
+                .unwrap()
+                .unwrap();
+            let aggregated = solve(&state, microlp, &aggregate).unwrap().unwrap();
+            assert!(
+                (plain.objective - aggregated.objective).abs() < 1e-6,
+                "{name}: {} != {}",
+                plain.objective,
+                aggregated.objective
+            );
+        }
+    }
+
+    #[test]
+    fn fragmentation_keeps_index_count_first() {
+        // Draining 1 and 2 takes two indices, 0 alone leaves one unit behind
+        let state = StateBuilder::new()
+            .group(|g| g.free_units([11, 5, 5]).request(10))
+            .build()
+            .unwrap();
+        for fragmentation in [512.0, 600.0, 1024.0] {
+            let options = SolveOptions {
+                fragmentation: Some(fragmentation),
+                ..Default::default()
+            };
+            let solution = solve(&state, microlp, &options).unwrap().unwrap();
+            assert_eq!(solution.groups, vec![vec![0]], "{fragmentation}");
+        }
+    }
+
+    #[test]
+    fn pareto_front_keeps_close_affinities() {
+        // The third index adds 0.5 to a large affinity
+        let state = StateBuilder::new()
+            .group(|g| g.free_units([1; 4]).request(2))
+            .connect((0, 0), (0, 1), 1e6)
+            .connect((0, 1), (0, 2), 0.5)
+            .build()
+            .unwrap();
+        let front = pareto_front(&state, microlp, &SolveOptions::default()).unwrap();
+        let points: Vec<_> = front
+            .iter()
+            .map(|s| (s.groups[0].clone(), affinity(&state, s)))
+            .collect();
+        assert_eq!(points, vec![(vec![0, 1], 1e6), (vec![0, 1, 2], 1e6 + 0.5)]);
+    }
+
+    #[test]
+    fn check_solution_cases() {
+        // Indices 0 and 1 must not be selected together
+        let state = StateBuilder::new()
+            .group(|g| {
+                g.free([
+                    Amount::new(1, 0),
+                    Amount::new(1, 0),
+                    Amount::new(1, 0),
+                    Amount::new(0, 5000),
+                ])
+                .request_amount(Amount::new(1, 5000))
+            })
+            .forbid((0, 0), (0, 1))
+            .build()
+            .unwrap();
+        let check = |indices: &[usize], amounts: &[(u32, u32)]| {
+            let solution = HqSolution {
+                groups: vec![indices.to_vec()],
+                amounts: vec![amounts.iter().map(|&(u, f)| Amount::new(u, f)).collect()],
+                objective: 0.0,
+            };
+            check_solution(&state, &solution)
+        };
+        check(&[0, 3], &[(1, 0), (0, 5000)]).unwrap();
+        check(&[0, 2], &[(1, 0), (0, 5000)]).unwrap();
+        for (indices, amounts, message) in [
+            (&[0, 3][..], &[(0, 5000), (1, 0)][..], "index 3 has"),
+            (&[0, 2, 3], &[(1, 0), (0, 2500), (0, 2500)], "requested"),
+            (&[0, 1], &[(1, 0), (0, 5000)], "Forbidden pair"),
+            (&[3, 0], &[(0, 5000), (1, 0)], "not sorted"),
+            (&[0, 3], &[(1, 0)], "differ in length"),
+        ] {
+            let error = check(indices, amounts).unwrap_err();
+            assert!(error.contains(message), "{indices:?}: {error}");
+        }
+    }
+
+    #[test]
+    fn forbidden_pair_never_selected() {
+        // Only {0, 2} avoids both forbidden pairs
+        let state = StateBuilder::new()
+            .group(|g| g.free_units([1; 3]).request(2))
+            .connect((0, 0), (0, 2), -10.0)
+            .forbid((0, 0), (0, 1))
+            .forbid((0, 1), (0, 2))
+            .build()
+            .unwrap();
+        let options = SolveOptions::default();
+        let solution = solve(&state, microlp, &options).unwrap().unwrap();
+        assert_eq!(solution.groups, vec![vec![0, 2]]);
+        assert_objective(&solution, -2.0 * 1024.0 - 10.0 - 2.0 / 32.0);
+        let mut all = state.clone();
+        all.groups[0].request = Amount::new_u(3);
+        assert!(solve(&all, microlp, &options).unwrap().is_none());
+    }
+
+    #[test]
+    fn negative_weight_avoided() {
+        // Without the penalty {0, 1} would win by leaving the larger index free
+        let state = StateBuilder::new()
+            .group(|g| g.free_units([2, 2, 3]).request(4))
+            .connect((0, 0), (0, 1), -50.0)
+            .build()
+            .unwrap();
+        let options = SolveOptions::default();
+        let solution = solve(&state, microlp, &options).unwrap().unwrap();
+        assert!(solution.groups[0].contains(&2));
+        assert_objective(&solution, -2.0 * 1024.0 - 5.0 / 32.0);
+        let mut all = state.clone();
+        all.groups[0].request = Amount::new_u(7);
+        let solution = solve(&all, microlp, &options).unwrap().unwrap();
+        assert_objective(&solution, -3.0 * 1024.0 - 50.0 - 7.0 / 32.0);
+    }
+
+    /// Four indices with one free unit, the first three in a hyper connection
+    fn hyper_state(request: u32, weight: f64) -> State {
+        StateBuilder::new()
+            .group(|g| g.free_units([1; 4]).request(request))
+            .hyper([(0, 0), (0, 1), (0, 2)], weight)
+            .build()
+            .unwrap()
+    }
+
+    fn assert_objective(solution: &HqSolution, expected: f64) {
+        let objective = solution.objective;
+        assert!(
+            (objective - expected).abs() < 1e-9,
+            "{objective} != {expected}"
+        );
+    }
+
+    #[test]
+    fn hyper_reward_needs_all_members() {
+        let options = SolveOptions::default();
+        let all = solve(&hyper_state(3, 100.0), microlp, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(all.groups, vec![vec![0, 1, 2]]);
+        assert_objective(&all, -3.0 * 1024.0 + 100.0 - 3.0 / 32.0);
+        let some = solve(&hyper_state(2, 100.0), microlp, &options)
+            .unwrap()
+            .unwrap();
+        assert_objective(&some, -2.0 * 1024.0 - 2.0 / 32.0);
+    }
+
+    #[test]
+    fn hyper_penalty_needs_all_members() {
+        let options = SolveOptions::default();
+        let avoided = solve(&hyper_state(3, -100.0), microlp, &options)
+            .unwrap()
+            .unwrap();
+        assert!(avoided.groups[0].contains(&3));
+        assert_objective(&avoided, -3.0 * 1024.0 - 3.0 / 32.0);
+        let all = solve(&hyper_state(4, -100.0), microlp, &options)
+            .unwrap()
+            .unwrap();
+        assert_objective(&all, -4.0 * 1024.0 - 100.0 - 4.0 / 32.0);
+    }
+
+    fn task(units: u32, priority: f64) -> Task {
+        Task {
+            requests: vec![Amount::new(units, 0)],
+            priority,
+        }
+    }
+
+    #[test]
+    fn tasks_accepted_by_priority() {
+        let state = StateBuilder::new()
+            .group(|g| g.free_units([2; 4]))
+            .build()
+            .unwrap();
+        let options = SolveOptions::default();
+        let low = solve_tasks(&state, &[task(3, 0.01)], microlp, &options)
+            .unwrap()
+            .unwrap();
+        assert!(low[0].is_some());
+        // Only one of the two fits
+        let tasks = [task(5, 0.2), task(5, 0.5)];
+        let solutions = solve_tasks(&state, &tasks, microlp, &options)
+            .unwrap()
+            .unwrap();
+        assert!(solutions[0].is_none());
+        assert_eq!(solutions[1].as_ref().unwrap().groups[0].len(), 3);
+    }
+
+    #[test]
+    fn tasks_invalid_input() {
+        let state = StateBuilder::new()
+            .group(|g| g.free_units([2; 4]))
+            .build()
+            .unwrap();
+        let two_requests = Task {
+            requests: vec![Amount::new(1, 0); 2],
+            priority: 1.0,
+        };
+        let tasks = [two_requests, task(1, 0.0), task(1, f64::NAN)];
+        let options = SolveOptions {
+            canonical: true,
+            ..Default::default()
+        };
+        let errors = solve_tasks(&state, &tasks, microlp, &options).unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [
+                ValidationError::TaskRequestCount { task: 0, .. },
+                ValidationError::InvalidPriority { task: 1, .. },
+                ValidationError::InvalidPriority { task: 2, .. },
+                ValidationError::UnsupportedOption {
+                    option: "canonical"
+                },
+            ]
+        ));
+    }
 }
@@ -1,9 +1,16 @@
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
+use crate::solver::HqSolution;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy)]
+const FRACTIONS_PER_UNIT: u64 = 10_000;
+
+/// Resource amount in whole units plus fractions of a unit (1/10000)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct Amount {
     pub units: u32,
+    #[serde(default)]
     pub fractions: u32,
 }
 
@@ -18,19 +25,118 @@ impl Amount {
     pub fn new(units: u32, fractions: u32) -> Self {
         Amount { units, fractions }
     }
+
+    /// Amount in fractions; a fraction may be cut from a whole unit, so amounts behave as
+    /// fixed-point numbers when allocated and released
+    pub fn as_fractions(&self) -> u64 {
+        self.units as u64 * FRACTIONS_PER_UNIT + self.fractions as u64
+    }
+
+    pub fn from_fractions(fractions: u64) -> Self {
+        Amount::new(
+            (fractions / FRACTIONS_PER_UNIT) as u32,
+            (fractions % FRACTIONS_PER_UNIT) as u32,
+        )
+    }
+
+    pub fn checked_sub(&self, other: &Amount) -> Option<Amount> {
+        Some(Amount::from_fractions(
+            self.as_fractions().checked_sub(other.as_fractions())?,
+        ))
+    }
+
+    pub fn checked_add(&self, other: &Amount) -> Option<Amount> {
+        let sum = self.as_fractions() + other.as_fractions();
+        u32::try_from(sum / FRACTIONS_PER_UNIT).ok()?;
+        Some(Amount::from_fractions(sum))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = String;
+
+    /// Parses `units` or `units.fractions`, e.g. `2.35` is 2 units and 3500 fractions
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid amount: {s}");
+        let (units, fractions) = s.split_once('.').unwrap_or((s, ""));
+        if fractions.len() > 4 || !fractions.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+        let units = units.parse().map_err(|_| err())?;
+        let fractions = format!("{fractions:0<4}").parse().map_err(|_| err())?;
+        Ok(Amount::new(units, fractions))
+    }
+}
+
+impl std::ops::Add for Amount {
+    type Output = Amount;
+
+    fn add(self, other: Amount) -> Amount {
+        Amount::from_fractions(self.as_fractions() + other.as_fractions())
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.fractions == 0 {
+            write!(f, "{}", self.units)
+        } else {
+            write!(f, "{}.{:04}", self.units, self.fractions)
+        }
+    }
 }
 
-#[derive(Debug)]
+/// Weighted link between index `g1` of group `r1` and index `g2` of group `r2`.
+/// The weight is counted once when both indices are selected. Both endpoints may lie
+/// in the same group (e.g. adjacent cores of one socket), but they must be different indices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Connection {
     pub r1: usize,
     pub g1: usize,
     pub r2: usize,
     pub g2: usize,
+    #[serde(with = "weight_serde")]
     pub weight: f64,
 }
 
+/// Weights are plain numbers, a forbidden pair is written as `"forbidden"`
+/// since JSON has no infinity
+mod weight_serde {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(weight: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if *weight == f64::NEG_INFINITY {
+            serializer.serialize_str("forbidden")
+        } else {
+            serializer.serialize_f64(*weight)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Weight {
+            Number(f64),
+            Name(String),
+        }
+        match Weight::deserialize(deserializer)? {
+            Weight::Number(weight) => Ok(weight),
+            Weight::Name(name) if name == "forbidden" => Ok(f64::NEG_INFINITY),
+            Weight::Name(name) => Err(serde::de::Error::custom(format!(
+                "invalid weight \"{name}\""
+            ))),
+        }
+    }
+}
+
 impl Connection {
+    /// Positive weight rewards selecting both indices (affinity),
+    /// negative weight penalizes it (anti-affinity).
     pub fn new(r1: usize, g1: usize, r2: usize, g2: usize, weight: f64) -> Self {
+        assert!(
+            (r1, g1) != (r2, g2),
+            "Connection of index {g1} in group {r1} to itself"
+        );
         Connection {
             r1,
             g1,
@@ -39,582 +145,717 @@ impl Connection {
             weight,
         }
     }
+
+    /// Hard constraint: both indices must never be selected together.
+    pub fn forbidden(r1: usize, g1: usize, r2: usize, g2: usize) -> Self {
+        Connection::new(r1, g1, r2, g2, f64::NEG_INFINITY)
+    }
+
+    pub fn is_forbidden(&self) -> bool {
+        self.weight == f64::NEG_INFINITY
+    }
+}
+
+/// Weight counted only when all member indices (group, index) are selected,
+/// e.g. all GPUs behind one NVLink switch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HyperConnection {
+    pub members: Vec<(usize, usize)>,
+    pub weight: f64,
+}
+
+impl HyperConnection {
+    pub fn new(members: Vec<(usize, usize)>, weight: f64) -> Self {
+        assert!(
+            members.len() >= 2,
+            "Hyper connection needs at least two members"
+        );
+        HyperConnection { members, weight }
+    }
 }
 
-#[derive(Debug)]
+/// Indices of one resource kind with their free amounts, and the amount requested from them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Group {
+    /// Optional name (e.g. `"gpus"`) to refer to the group instead of its position
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub free: Vec<Amount>,
+    #[serde(default)]
     pub request: Amount,
+    /// Optional label per index (e.g. `"gpu3"`); either empty or as long as `free`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<Option<String>>,
+}
+
+impl Group {
+    /// Position of the index with the given label; an unlabelled index can also be referred to
+    /// by its position written as a string
+    pub fn index_by_label(&self, label: &str) -> Option<usize> {
+        self.labels
+            .iter()
+            .position(|l| l.as_deref() == Some(label))
+            .or_else(|| {
+                let g = label.parse::<usize>().ok()?;
+                (g < self.free.len() && self.labels.get(g).is_none_or(|l| l.is_none())).then_some(g)
+            })
+    }
+}
+
+/// One of several tasks allocated together by `solve_tasks`, with a request for every group.
+#[derive(Debug, Clone)]
+pub struct Task {
+    pub requests: Vec<Amount>,
+    pub priority: f64,
 }
 
-#[derive(Debug)]
+/// Node topology: groups with free amounts and requests, and connections between indices.
+/// In instance files, connection endpoints and hyper connection members may also be given by
+/// group name and index label, e.g. `{"r1": "gpus", "g1": "gpu3", ...}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "StateFile")]
 pub struct State {
     pub groups: Vec<Group>,
+    #[serde(default)]
     pub connections: Vec<Connection>,
+    #[serde(default)]
+    pub hyper_connections: Vec<HyperConnection>,
 }
 
-pub fn create_empty_4() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![Amount::new_u(4); 4],
-            request: Amount::new_u(7),
-        }],
-        connections: Vec::new(),
-    }
+/// Group or index in an instance file, by position or by name
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NameOrPosition {
+    Position(usize),
+    Name(String),
 }
 
-pub fn create_empty_16() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![Amount::new_u(24); 16],
-            request: Amount::new_u(100),
-        }],
-        connections: Vec::new(),
-    }
+#[derive(Deserialize)]
+struct ConnectionFile {
+    r1: NameOrPosition,
+    g1: NameOrPosition,
+    r2: NameOrPosition,
+    g2: NameOrPosition,
+    #[serde(with = "weight_serde")]
+    weight: f64,
 }
 
-pub fn create_empty_8_8() -> State {
-    State {
-        groups: vec![
-            Group {
-                free: vec![Amount::new_u(24); 8],
-                request: Amount::new_u(12),
-            },
-            Group {
-                free: vec![Amount::new_u(32); 8],
-                request: Amount::new_u(74),
-            },
-        ],
-        connections: Vec::new(),
-    }
+#[derive(Deserialize)]
+struct HyperConnectionFile {
+    members: Vec<(NameOrPosition, NameOrPosition)>,
+    weight: f64,
 }
 
-pub fn create_empty_8_8_8() -> State {
-    State {
-        groups: vec![
-            Group {
-                free: vec![Amount::new_u(24); 8],
-                request: Amount::new_u(48),
-            },
-            Group {
-                free: vec![Amount::new_u(32); 8],
-                request: Amount::new_u(74),
-            },
-            Group {
-                free: vec![Amount::new_u(4); 8],
-                request: Amount::new_u(30),
-            },
-        ],
-        connections: Vec::new(),
-    }
+#[derive(Deserialize)]
+struct StateFile {
+    groups: Vec<Group>,
+    #[serde(default)]
+    connections: Vec<ConnectionFile>,
+    #[serde(default)]
+    hyper_connections: Vec<HyperConnectionFile>,
 }
 
-pub fn create_empty_32() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![Amount::new_u(120); 32],
-            request: Amount::new_u(921),
-        }],
-        connections: Vec::new(),
+impl TryFrom<StateFile> for State {
+    type Error = ValidationError;
+
+    fn try_from(file: StateFile) -> Result<Self, Self::Error> {
+        let mut state = State {
+            groups: file.groups,
+            connections: Vec::new(),
+            hyper_connections: Vec::new(),
+        };
+        // Positions out of range are left for `validate`
+        let resolve = |state: &State, r: NameOrPosition, g: NameOrPosition| {
+            let r = match r {
+                NameOrPosition::Position(r) => r,
+                NameOrPosition::Name(name) => {
+                    state
+                        .group_by_name(&name)
+                        .ok_or(ValidationError::UnknownName {
+                            group: name,
+                            index: None,
+                        })?
+                }
+            };
+            let g = match g {
+                NameOrPosition::Position(g) => g,
+                NameOrPosition::Name(label) => state
+                    .groups
+                    .get(r)
+                    .and_then(|group| group.index_by_label(&label))
+                    .ok_or_else(|| ValidationError::UnknownName {
+                        group: state
+                            .groups
+                            .get(r)
+                            .map_or(r.to_string(), |_| state.group_name(r)),
+                        index: Some(label),
+                    })?,
+            };
+            Ok((r, g))
+        };
+        for c in file.connections {
+            let (r1, g1) = resolve(&state, c.r1, c.g1)?;
+            let (r2, g2) = resolve(&state, c.r2, c.g2)?;
+            state.connections.push(Connection {
+                r1,
+                g1,
+                r2,
+                g2,
+                weight: c.weight,
+            });
+        }
+        for h in file.hyper_connections {
+            let members = h
+                .members
+                .into_iter()
+                .map(|(r, g)| resolve(&state, r, g))
+                .collect::<Result<_, _>>()?;
+            state.hyper_connections.push(HyperConnection {
+                members,
+                weight: h.weight,
+            });
+        }
+        Ok(state)
     }
 }
 
-pub fn create_onlyo_16() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![
-                Amount::new_u(4),
-                Amount::new_u(0),
-                Amount::new_u(2),
-                Amount::new_u(1),
-                Amount::new_u(4),
-                Amount::new_u(0),
-                Amount::new_u(2),
-                Amount::new_u(1),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(3),
-                Amount::new_u(2),
-                Amount::new_u(3),
-                Amount::new_u(2),
-            ],
-            request: Amount::new_u(24),
-        }],
-        connections: Vec::new(),
-    }
+/// Problem found by `State::validate` or `State::validate_tasks`. Connections and tasks are
+/// referred to by their position in `connections`, `hyper_connections` or the tasks.
+#[derive(Debug, Clone)]
+pub enum ValidationError {
+    /// Free amount (`index` is Some) or request (`index` is None) with a whole unit or more in
+    /// fractions
+    Fractions {
+        group: usize,
+        index: Option<usize>,
+        fractions: u32,
+    },
+    UnknownEndpoint {
+        connection: usize,
+        group: usize,
+        index: usize,
+    },
+    SelfConnection {
+        connection: usize,
+    },
+    /// Second connection between the same two indices
+    DuplicateConnection {
+        first: usize,
+        second: usize,
+    },
+    /// NaN or positive infinity; negative infinity marks a forbidden pair
+    InvalidWeight {
+        connection: usize,
+        weight: f64,
+    },
+    UnknownHyperMember {
+        hyper: usize,
+        group: usize,
+        index: usize,
+    },
+    /// Fewer than two distinct members
+    SmallHyperConnection {
+        hyper: usize,
+    },
+    /// NaN or infinite
+    InvalidHyperWeight {
+        hyper: usize,
+        weight: f64,
+    },
+    /// Group with labels for some but not all of its indices
+    LabelCount {
+        group: usize,
+        labels: usize,
+        indices: usize,
+    },
+    /// Reference to a group name or index label that does not exist
+    UnknownName {
+        group: String,
+        index: Option<String>,
+    },
+    /// Group name, or index label within a group, used twice
+    DuplicateName {
+        name: String,
+    },
+    /// Task without exactly one request per group
+    TaskRequestCount {
+        task: usize,
+        requests: usize,
+        groups: usize,
+    },
+    /// Zero, negative, NaN or infinite
+    InvalidPriority {
+        task: usize,
+        priority: f64,
+    },
+    /// Field of `SolveOptions` that `solve_tasks` does not support
+    UnsupportedOption {
+        option: &'static str,
+    },
 }
 
-pub fn create_onlyo_4() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![
-                Amount::new_u(1),
-                Amount::new_u(0),
-                Amount::new_u(2),
-                Amount::new_u(1),
-            ],
-            request: Amount::new_u(7),
-        }],
-        connections: Vec::new(),
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Fractions {
+                group,
+                index: Some(index),
+                fractions,
+            } => write!(
+                f,
+                "index {index} of group {group} has {fractions} free fractions, \
+                 at most {} allowed",
+                FRACTIONS_PER_UNIT - 1
+            ),
+            ValidationError::Fractions {
+                group,
+                index: None,
+                fractions,
+            } => write!(
+                f,
+                "request of group {group} has {fractions} fractions, at most {} allowed",
+                FRACTIONS_PER_UNIT - 1
+            ),
+            ValidationError::UnknownEndpoint {
+                connection,
+                group,
+                index,
+            } => write!(
+                f,
+                "connection {connection} refers to index {index} of group {group}, \
+                 which does not exist"
+            ),
+            ValidationError::SelfConnection { connection } => {
+                write!(f, "connection {connection} connects an index to itself")
+            }
+            ValidationError::DuplicateConnection { first, second } => write!(
+                f,
+                "connections {first} and {second} connect the same indices"
+            ),
+            ValidationError::InvalidWeight { connection, weight } => {
+                write!(f, "connection {connection} has invalid weight {weight}")
+            }
+            ValidationError::UnknownHyperMember {
+                hyper,
+                group,
+                index,
+            } => write!(
+                f,
+                "hyper connection {hyper} refers to index {index} of group {group}, \
+                 which does not exist"
+            ),
+            ValidationError::SmallHyperConnection { hyper } => write!(
+                f,
+                "hyper connection {hyper} has fewer than two distinct members"
+            ),
+            ValidationError::InvalidHyperWeight { hyper, weight } => {
+                write!(f, "hyper connection {hyper} has invalid weight {weight}")
+            }
+            ValidationError::LabelCount {
+                group,
+                labels,
+                indices,
+            } => write!(f, "group {group} has {labels} labels for {indices} indices"),
+            ValidationError::UnknownName { group, index: None } => {
+                write!(f, "unknown group \"{group}\"")
+            }
+            ValidationError::UnknownName {
+                group,
+                index: Some(index),
+            } => write!(f, "unknown index \"{index}\" in group \"{group}\""),
+            ValidationError::DuplicateName { name } => write!(f, "name \"{name}\" used twice"),
+            ValidationError::TaskRequestCount {
+                task,
+                requests,
+                groups,
+            } => write!(f, "task {task} has {requests} requests for {groups} groups"),
+            ValidationError::InvalidPriority { task, priority } => write!(
+                f,
+                "task {task} has priority {priority}, it must be positive"
+            ),
+            ValidationError::UnsupportedOption { option } => {
+                write!(f, "option {option} is not supported for tasks")
+            }
+        }
     }
 }
 
-pub fn create_onlyo_32() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![
-                Amount::new_u(4),
-                Amount::new_u(0),
-                Amount::new_u(2),
-                Amount::new_u(1),
-                Amount::new_u(4),
-                Amount::new_u(0),
-                Amount::new_u(2),
-                Amount::new_u(1),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(3),
-                Amount::new_u(2),
-                Amount::new_u(3),
-                Amount::new_u(2),
-                Amount::new_u(4),
-                Amount::new_u(0),
-                Amount::new_u(2),
-                Amount::new_u(1),
-                Amount::new_u(4),
-                Amount::new_u(0),
-                Amount::new_u(2),
-                Amount::new_u(1),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(0),
-                Amount::new_u(3),
-                Amount::new_u(2),
-                Amount::new_u(3),
-                Amount::new_u(2),
-            ],
-            request: Amount::new_u(48),
-        }],
-        connections: Vec::new(),
+impl State {
+    /// Position of the group with the given name
+    pub fn group_by_name(&self, name: &str) -> Option<usize> {
+        self.groups
+            .iter()
+            .position(|g| g.name.as_deref() == Some(name))
     }
-}
 
-pub fn create_primes_16() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![
-                Amount::new_u(2),
-                Amount::new_u(3),
-                Amount::new_u(5),
-                Amount::new_u(7),
-                Amount::new_u(9),
-                Amount::new_u(11),
-                Amount::new_u(13),
-                Amount::new_u(17),
-                Amount::new_u(19),
-                Amount::new_u(23),
-                Amount::new_u(29),
-                Amount::new_u(31),
-                Amount::new_u(37),
-                Amount::new_u(39),
-                Amount::new_u(41),
-            ],
-            request: Amount::new_u(113),
-        }],
-        connections: Vec::new(),
+    /// Position of an index given by group name and index label (see `Group::index_by_label`)
+    pub fn index_by_name(
+        &self,
+        group: &str,
+        index: &str,
+    ) -> Result<(usize, usize), ValidationError> {
+        let r = self
+            .group_by_name(group)
+            .ok_or_else(|| ValidationError::UnknownName {
+                group: group.to_string(),
+                index: None,
+            })?;
+        let g =
+            self.groups[r]
+                .index_by_label(index)
+                .ok_or_else(|| ValidationError::UnknownName {
+                    group: group.to_string(),
+                    index: Some(index.to_string()),
+                })?;
+        Ok((r, g))
     }
-}
 
-pub fn create_primes_16_n() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![
-                Amount::new_u(2),
-                Amount::new_u(3),
-                Amount::new_u(5),
-                Amount::new_u(7),
-                Amount::new_u(9),
-                Amount::new_u(11),
-                Amount::new_u(13),
-                Amount::new_u(17),
-                Amount::new_u(19),
-                Amount::new_u(23),
-                Amount::new_u(29),
-                Amount::new_u(31),
-                Amount::new_u(37),
-                Amount::new_u(39),
-                Amount::new_u(41),
-            ],
-            request: Amount::new_u(329),
-        }],
-        connections: Vec::new(),
+    pub fn has_names(&self) -> bool {
+        self.groups
+            .iter()
+            .any(|g| g.name.is_some() || g.labels.iter().any(|l| l.is_some()))
     }
-}
 
-pub fn create_triplets_16() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![
-                Amount::new_u(40),
-                Amount::new_u(41),
-                Amount::new_u(42),
-                Amount::new_u(40),
-                Amount::new_u(41),
-                Amount::new_u(42),
-                Amount::new_u(39),
-                Amount::new_u(40),
-                Amount::new_u(41),
-                Amount::new_u(43),
-                Amount::new_u(44),
-                Amount::new_u(45),
-                Amount::new_u(38),
-                Amount::new_u(39),
-                Amount::new_u(40),
-            ],
-            request: Amount::new_u(399),
-        }],
-        connections: Vec::new(),
+    /// Name of a group for output, its position if it has no name
+    pub fn group_name(&self, r: usize) -> String {
+        self.groups[r].name.clone().unwrap_or_else(|| r.to_string())
     }
-}
 
-pub fn create_one2one_12_12() -> State {
-    State {
-        groups: vec![
-            Group {
-                free: vec![
-                    Amount::new_u(4),
-                    Amount::new_u(4),
-                    Amount::new_u(4),
-                    Amount::new_u(4),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(2),
-                    Amount::new_u(5),
-                    Amount::new_u(1),
-                ],
-                request: Amount::new_u(7),
-            },
-            Group {
-                free: vec![
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(1),
-                    Amount::new_u(0),
-                    Amount::new_u(0),
-                    Amount::new_u(1),
-                ],
-                request: Amount::new_u(7),
-            },
-        ],
-        connections: (0..12)
-            .map(|i| Connection::new(0, i, 1, i, 256.0))
-            .collect(),
+    /// Label of an index for output, its position if it has no label
+    pub fn index_label(&self, r: usize, g: usize) -> String {
+        match self.groups[r].labels.get(g) {
+            Some(Some(label)) => label.clone(),
+            _ => g.to_string(),
+        }
     }
-}
 
-pub fn create_one2one_8_8_8() -> State {
-    State {
-        groups: vec![
-            Group {
-                free: vec![
-                    Amount::new_u(4),
-                    Amount::new_u(4),
-                    Amount::new_u(4),
-                    Amount::new_u(4),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                ],
-                request: Amount::new_u(7),
-            },
-            Group {
-                free: vec![
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                ],
-                request: Amount::new_u(7),
-            },
-            Group {
-                free: vec![
-                    Amount::new_u(2),
-                    Amount::new_u(6),
-                    Amount::new_u(2),
-                    Amount::new_u(3),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                ],
-                request: Amount::new_u(12),
-            },
-        ],
-        connections: (0..8)
-            .map(|i| Connection::new(0, i, 1, i, 256.0))
-            .chain((0..8).map(|i| Connection::new(1, i, 2, i, 256.0)))
-            .collect(),
+    /// Checks that all connections refer to existing indices, weights are numbers and amounts
+    /// are normalized. Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let exists =
+            |r: usize, g: usize| self.groups.get(r).is_some_and(|group| g < group.free.len());
+
+        let mut names = HashMap::new();
+        for (r, group) in self.groups.iter().enumerate() {
+            if let Some(name) = &group.name
+                && names.insert(name, r).is_some()
+            {
+                errors.push(ValidationError::DuplicateName { name: name.clone() });
+            }
+            if !group.labels.is_empty() && group.labels.len() != group.free.len() {
+                errors.push(ValidationError::LabelCount {
+                    group: r,
+                    labels: group.labels.len(),
+                    indices: group.free.len(),
+                });
+            }
+            let mut labels: Vec<&String> = group.labels.iter().flatten().collect();
+            labels.sort();
+            for w in labels.windows(2).filter(|w| w[0] == w[1]) {
+                errors.push(ValidationError::DuplicateName { name: w[0].clone() });
+            }
+            let amounts = group.free.iter().enumerate().map(|(i, a)| (Some(i), a));
+            for (index, amount) in amounts.chain([(None, &group.request)]) {
+                if amount.fractions as u64 >= FRACTIONS_PER_UNIT {
+                    errors.push(ValidationError::Fractions {
+                        group: r,
+                        index,
+                        fractions: amount.fractions,
+                    });
+                }
+            }
+        }
+
+        let mut pairs = HashMap::new();
+        for (n, c) in self.connections.iter().enumerate() {
+            for (group, index) in [(c.r1, c.g1), (c.r2, c.g2)] {
+                if !exists(group, index) {
+                    errors.push(ValidationError::UnknownEndpoint {
+                        connection: n,
+                        group,
+                        index,
+                    });
+                }
+            }
+            if (c.r1, c.g1) == (c.r2, c.g2) {
+                errors.push(ValidationError::SelfConnection { connection: n });
+            }
+            if c.weight.is_nan() || c.weight == f64::INFINITY {
+                errors.push(ValidationError::InvalidWeight {
+                    connection: n,
+                    weight: c.weight,
+                });
+            }
+            let key = (
+                (c.r1, c.g1).min((c.r2, c.g2)),
+                (c.r1, c.g1).max((c.r2, c.g2)),
+            );
+            if let Some(&first) = pairs.get(&key) {
+                errors.push(ValidationError::DuplicateConnection { first, second: n });
+            } else {
+                pairs.insert(key, n);
+            }
+        }
+
+        for (n, h) in self.hyper_connections.iter().enumerate() {
+            for &(group, index) in &h.members {
+                if !exists(group, index) {
+                    errors.push(ValidationError::UnknownHyperMember {
+                        hyper: n,
+                        group,
+                        index,
+                    });
+                }
+            }
+            let mut members = h.members.clone();
+            members.sort();
+            members.dedup();
+            if members.len() < 2 {
+                errors.push(ValidationError::SmallHyperConnection { hyper: n });
+            }
+            if !h.weight.is_finite() {
+                errors.push(ValidationError::InvalidHyperWeight {
+                    hyper: n,
+                    weight: h.weight,
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
-}
 
-pub fn create_all2all_16_16() -> State {
-    State {
-        groups: vec![
-            Group {
-                free: vec![
-                    Amount::new_u(1),
-                    Amount::new_u(4),
-                    Amount::new_u(3),
-                    Amount::new_u(4),
-                    Amount::new_u(5),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(1),
-                    Amount::new_u(4),
-                    Amount::new_u(3),
-                    Amount::new_u(4),
-                    Amount::new_u(5),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                ],
-                request: Amount::new_u(14),
-            },
-            Group {
-                free: vec![
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(0),
-                    Amount::new_u(0),
-                    Amount::new_u(0),
-                    Amount::new_u(5),
-                    Amount::new_u(1),
-                    Amount::new_u(3),
-                    Amount::new_u(1),
-                    Amount::new_u(1),
-                ],
-                request: Amount::new_u(9),
-            },
-        ],
-        connections: (0..16)
-            .flat_map(|i| {
-                (0..i)
-                    .map(move |j| Connection::new(0, i, 1, j, ((i + j) % 2) as f64 * 128.0 + 128.0))
-            })
-            .collect(),
+    /// Checks the state like `validate`, and that every task has one request per group and a
+    /// positive priority
+    pub fn validate_tasks(&self, tasks: &[Task]) -> Result<(), Vec<ValidationError>> {
+        let mut errors = self.validate().err().unwrap_or_default();
+        for (t, task) in tasks.iter().enumerate() {
+            if task.requests.len() != self.groups.len() {
+                errors.push(ValidationError::TaskRequestCount {
+                    task: t,
+                    requests: task.requests.len(),
+                    groups: self.groups.len(),
+                });
+            }
+            if !(task.priority > 0.0 && task.priority.is_finite()) {
+                errors.push(ValidationError::InvalidPriority {
+                    task: t,
+                    priority: task.priority,
+                });
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
-}
 
-pub fn create_stairs_32_16_8() -> State {
-    State {
-        groups: vec![
-            Group {
-                free: (0..32).map(|i| Amount::new_u(i)).collect(),
-                request: Amount::new_u(127),
-            },
-            Group {
-                free: vec![
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(0),
-                    Amount::new_u(0),
-                    Amount::new_u(0),
-                    Amount::new_u(5),
-                    Amount::new_u(1),
-                    Amount::new_u(3),
-                    Amount::new_u(1),
-                    Amount::new_u(1),
-                ],
-                request: Amount::new_u(19),
-            },
-            Group {
-                free: vec![
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(2),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(3),
-                    Amount::new_u(5),
-                ],
-                request: Amount::new_u(10),
-            },
-        ],
-        connections: (0..16)
-            .flat_map(|i| (0..1).map(move |j| Connection::new(0, i * 2 + j, 1, i, 256.0)))
-            .chain(
-                (0..8)
-                    .flat_map(|i| (0..1).map(move |j| Connection::new(1, i * 2 + j, 2, i, 348.0))),
-            )
-            .collect(),
+    pub fn set_requests(&mut self, requests: &[Amount]) {
+        for (group, request) in self.groups.iter_mut().zip(requests) {
+            group.request = *request;
+        }
     }
-}
 
-pub fn create_diamonds_32_16_32() -> State {
-    State {
-        groups: vec![
-            Group {
-                free: (0..32).map(|i| Amount::new_u(4)).collect(),
-                request: Amount::new_u(7),
-            },
-            Group {
-                free: (0..32).map(|i| Amount::new_u(4)).collect(),
-                request: Amount::new_u(7),
-            },
-            Group {
-                free: (0..32).map(|i| Amount::new_u(4)).collect(),
-                request: Amount::new_u(7),
-            },
-        ],
-        connections: (0..16)
-            .flat_map(|i| (0..1).map(move |j| Connection::new(0, i * 2 + j, 1, i, 256.0)))
-            .chain(
-                (0..16)
-                    .flat_map(|i| (0..1).map(move |j| Connection::new(2, i * 2 + j, 1, i, 348.0))),
-            )
-            .collect(),
+    /// Takes the allocated amounts of a solution from the free amounts.
+    /// Nothing is changed when some index does not have enough free resources.
+    pub fn allocate(&mut self, solution: &HqSolution) -> Result<(), String> {
+        self.update_free(solution, "allocate", Amount::checked_sub)
+    }
+
+    /// Returns the allocated amounts of a solution back to the free amounts.
+    /// Nothing is changed when some index does not exist or its free amount would overflow.
+    pub fn release(&mut self, solution: &HqSolution) -> Result<(), String> {
+        self.update_free(solution, "release", Amount::checked_add)
     }
-}
 
-pub fn create_random_18_18(seed: u64, p: f64) -> State {
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut connections = Vec::new();
-    for i in 0..18 {
-        for j in 0..i {
-            if rng.random_bool(p) {
-                connections.push(Connection::new(
-                    0,
-                    i,
-                    1,
-                    j,
-                    64.0 * rng.random_range(1..6) as f64,
-                ))
+    /// Applies `update` to the free amount of every selected index, on a copy so that an error
+    /// leaves the state unchanged. An index listed twice is updated twice.
+    fn update_free(
+        &mut self,
+        solution: &HqSolution,
+        action: &str,
+        update: impl Fn(&Amount, &Amount) -> Option<Amount>,
+    ) -> Result<(), String> {
+        let mut free: Vec<Vec<Amount>> = self.groups.iter().map(|g| g.free.clone()).collect();
+        for (r, (indices, amounts)) in solution.groups.iter().zip(&solution.amounts).enumerate() {
+            for (&i, amount) in indices.iter().zip(amounts) {
+                let free = free
+                    .get_mut(r)
+                    .and_then(|f| f.get_mut(i))
+                    .ok_or_else(|| format!("Index {i} of group {r} does not exist"))?;
+                *free = update(free, amount).ok_or_else(|| {
+                    format!("Index {i} of group {r} has {free} free, cannot {action} {amount}")
+                })?;
             }
         }
-    }
-    State {
-        groups: vec![
-            Group {
-                free: (0..18)
-                    .map(|i| Amount::new_u(rng.random_range(1..=4)))
-                    .collect(),
-                request: Amount::new_u(12),
-            },
-            Group {
-                free: (0..18)
-                    .map(|i| Amount::new_u(rng.random_range(1..=4)))
-                    .collect(),
-                request: Amount::new_u(12),
-            },
-        ],
-        connections,
+        for (group, free) in self.groups.iter_mut().zip(free) {
+            group.free = free;
+        }
+        Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::StateBuilder;
+    use crate::solver::{self, SolveOptions};
+    use good_lp::solvers::microlp::microlp;
 
-pub fn create_empty_f_16() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![Amount::new_u(24); 8],
-            request: Amount::new(100, 500),
-        }],
-        connections: Vec::new(),
+    #[test]
+    fn same_group_connection_counted_once() {
+        let state = StateBuilder::new()
+            .group(|g| g.free_units([1; 4]).request(2))
+            .connect((0, 0), (0, 1), 10.0)
+            .build()
+            .unwrap();
+        let solution = solver::solve(&state, microlp, &SolveOptions::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(solution.groups, vec![vec![0, 1]]);
+        assert_eq!(solver::affinity(&state, &solution), 10.0);
+        assert_eq!(solution.objective, -2.0 * 1024.0 + 10.0 - 2.0 / 32.0);
     }
-}
 
-pub fn create_fractions_16() -> State {
-    State {
-        groups: vec![Group {
-            free: vec![
-                Amount::new(2, 5000), // 0
-                Amount::new(1, 0),
-                Amount::new(2, 5500),
-                Amount::new(1, 2500),
-                Amount::new(1, 3000), // 4
-                Amount::new(0, 3600),
-                Amount::new(8, 0),
-                Amount::new(1, 6500),
-                Amount::new(2, 5000), // 8
-                Amount::new(1, 0),
-                Amount::new(2, 9000),
-                Amount::new(1, 2500),
-                Amount::new(1, 3000), // 12
-                Amount::new(0, 3500),
-                Amount::new(0, 9500),
-                Amount::new(1, 4500),
-
-            ],
-            request: Amount::new(12, 3500),
-        }],
-        connections: Vec::new(),
+    #[test]
+    #[should_panic(expected = "to itself")]
+    fn self_connection_panics() {
+        Connection::new(0, 1, 0, 1, 1.0);
     }
-}
 
-pub fn create_random_f_18_18(seed: u64, p: f64) -> State {
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut connections = Vec::new();
-    for i in 0..18 {
-        for j in 0..i {
-            if rng.random_bool(p) {
-                connections.push(Connection::new(
-                    0,
-                    i,
-                    1,
-                    j,
-                    64.0 * rng.random_range(1..6) as f64,
-                ))
-            }
+    #[test]
+    fn self_connection_is_invalid() {
+        let mut state = StateBuilder::new()
+            .group(|g| g.free_units([1; 2]).request(1))
+            .build()
+            .unwrap();
+        state.connections.push(Connection {
+            r1: 0,
+            g1: 1,
+            r2: 0,
+            g2: 1,
+            weight: 1.0,
+        });
+        let errors = state.validate().unwrap_err();
+        assert!(matches!(
+            errors[..],
+            [ValidationError::SelfConnection { connection: 0 }]
+        ));
+    }
+
+    #[test]
+    fn release_foreign_solution() {
+        let mut state = StateBuilder::new()
+            .group(|g| g.free_units([1; 2]).request(1))
+            .build()
+            .unwrap();
+        let solution = HqSolution {
+            groups: vec![vec![1, 2]],
+            amounts: vec![vec![Amount::new_u(1); 2]],
+            objective: 0.0,
+        };
+        assert!(state.release(&solution).is_err());
+        assert!(state.groups[0].free.iter().all(|f| f.units == 1));
+    }
+
+    /// Group "gpus" with indices "a", unlabelled and "c", and an unnamed group with two indices
+    fn named_state(connections: &str, hyper: &str) -> Result<State, serde_json::Error> {
+        let one = r#"{"units": 1, "fractions": 0}"#;
+        serde_json::from_str(&format!(
+            r#"{{
+                "groups": [
+                    {{"name": "gpus", "free": [{one}, {one}, {one}], "labels": ["a", null, "c"]}},
+                    {{"free": [{one}, {one}]}}
+                ],
+                "connections": [{connections}],
+                "hyper_connections": [{hyper}]
+            }}"#
+        ))
+    }
+
+    #[test]
+    fn names_resolved() {
+        let state = named_state(
+            r#"{"r1": "gpus", "g1": "c", "r2": 1, "g2": 0, "weight": 2.0}"#,
+            r#"{"members": [["gpus", "a"], ["gpus", "1"], [1, "1"]], "weight": 3.0}"#,
+        )
+        .unwrap();
+        let c = &state.connections[0];
+        assert_eq!((c.r1, c.g1, c.r2, c.g2), (0, 2, 1, 0));
+        assert_eq!(
+            state.hyper_connections[0].members,
+            vec![(0, 0), (0, 1), (1, 1)]
+        );
+        state.validate().unwrap();
+    }
+
+    #[test]
+    fn unknown_names() {
+        for (connection, message) in [
+            (r#""r1": "cpus", "g1": 0"#, "unknown group \"cpus\""),
+            (
+                r#""r1": "gpus", "g1": "b""#,
+                "unknown index \"b\" in group \"gpus\"",
+            ),
+            // Position 0 has the label "a", so it is not found by position
+            (r#""r1": "gpus", "g1": "0""#, "unknown index \"0\""),
+            (r#""r1": 0, "g1": "3""#, "unknown index \"3\""),
+        ] {
+            let connection = format!(r#"{{{connection}, "r2": 1, "g2": 0, "weight": 1.0}}"#);
+            let error = named_state(&connection, "").unwrap_err().to_string();
+            assert!(error.contains(message), "{error}");
         }
+        let error = named_state("", r#"{"members": [[1, 0], [1, "x"]], "weight": 1.0}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("unknown index \"x\" in group \"1\""),
+            "{error}"
+        );
     }
-    State {
-        groups: vec![
-            Group {
-                free: (0..18)
-                    .map(|i| Amount::new(rng.random_range(1..=4), rng.random_range(0..10_000)))
-                    .collect(),
-                request: Amount::new(12, 4999),
-            },
-            Group {
-                free: (0..18)
-                    .map(|i| Amount::new_u(rng.random_range(1..=4)))
-                    .collect(),
-                request: Amount::new(12, 6010),
-            },
-        ],
-        connections,
+
+    #[test]
+    fn invalid_names() {
+        let mut state = named_state("", "").unwrap();
+        state.groups[1].name = Some("gpus".to_string());
+        state.groups[1].labels = vec![Some("x".to_string())];
+        state.groups[0].labels[1] = Some("a".to_string());
+        let errors = state.validate().unwrap_err();
+        assert!(matches!(
+            &errors[..],
+            [
+                ValidationError::DuplicateName { name: label },
+                ValidationError::DuplicateName { name: group },
+                ValidationError::LabelCount {
+                    group: 1,
+                    labels: 1,
+                    indices: 2
+                },
+            ] if label == "a" && group == "gpus"
+        ));
     }
-}
 
+    #[test]
+    fn allocate_duplicate_index() {
+        let mut state = StateBuilder::new()
+            .group(|g| g.free_units([1, 2]).request(1))
+            .build()
+            .unwrap();
+        let twice = |i| HqSolution {
+            groups: vec![vec![i, i]],
+            amounts: vec![vec![Amount::new_u(1); 2]],
+            objective: 0.0,
+        };
+        assert!(state.allocate(&twice(0)).is_err());
+        assert_eq!(state.groups[0].free[0].units, 1);
+        state.allocate(&twice(1)).unwrap();
+        assert_eq!(state.groups[0].free[1].units, 0);
+        state.release(&twice(1)).unwrap();
+        assert_eq!(state.groups[0].free[1].units, 2);
+    }
+}